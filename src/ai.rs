@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::{math::*, prelude::*};
+
+use crate::{
+    character::{CharacterConfig, MovementIntent},
+    level::LevelEntity,
+    navigation::NavMesh,
+    AppState, GameAssets,
+};
+
+const NPC_STARTING_TRANSLATION: Vec3 = Vec3::new(3.0, 10.0, 3.0);
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Main), spawn_npcs)
+            .add_systems(Update, patrol.run_if(in_state(AppState::Main)));
+    }
+}
+
+/// Makes a character walk between waypoints in a loop.
+///
+/// Once the [`NavMesh`] is baked, the character follows a path around
+/// obstacles to each waypoint instead of heading straight for it.
+#[derive(Component)]
+pub struct Patrol {
+    pub waypoints: Vec<Vector>,
+    pub current: usize,
+    /// How close the character has to get to a waypoint before heading to the next one.
+    pub arrival_radius: Scalar,
+    path: Vec<Vector>,
+    /// The waypoint the path was last searched for. A search isn't repeated
+    /// until the waypoint or the [`NavMesh`] changes, even if it failed.
+    searched: Option<usize>,
+}
+
+impl Patrol {
+    pub fn new(waypoints: Vec<Vector>) -> Self {
+        Self {
+            waypoints,
+            current: 0,
+            arrival_radius: 0.5,
+            path: Vec::new(),
+            searched: None,
+        }
+    }
+}
+
+fn spawn_npcs(
+    mut commands: Commands,
+    scene_assets: Res<GameAssets>,
+    configs: Res<Assets<CharacterConfig>>,
+) {
+    let config = configs
+        .get(&scene_assets.npc_config)
+        .cloned()
+        .unwrap_or_default();
+
+    commands.spawn((
+        SceneBundle {
+            scene: scene_assets.character.clone(),
+            transform: Transform::from_translation(NPC_STARTING_TRANSLATION),
+            ..default()
+        },
+        config.controller_bundle(),
+        scene_assets.npc_config.clone(),
+        Patrol::new(vec![
+            Vector::new(3.0, 0.0, 3.0),
+            Vector::new(-3.0, 0.0, 3.0),
+            Vector::new(-3.0, 0.0, -3.0),
+            Vector::new(3.0, 0.0, -3.0),
+        ]),
+        LevelEntity,
+        DebugRender::default().with_collider_color(Color::BLUE),
+    ));
+}
+
+/// Steers patrolling characters towards their current waypoint.
+fn patrol(nav_mesh: Res<NavMesh>, mut query: Query<(&Position, &mut Patrol, &mut MovementIntent)>) {
+    // Points are only compared on the ground plane
+    let horizontal = |vector: Vector| vector * Vector::new(1.0, 0.0, 1.0);
+
+    for (position, mut patrol, mut intent) in &mut query {
+        let Some(&waypoint) = patrol.waypoints.get(patrol.current) else {
+            intent.direction = Vector::ZERO;
+            continue;
+        };
+
+        if horizontal(waypoint - position.0).length() <= patrol.arrival_radius {
+            patrol.current = (patrol.current + 1) % patrol.waypoints.len();
+            patrol.path.clear();
+            patrol.searched = None;
+            continue;
+        }
+
+        if patrol.path.is_empty()
+            && (patrol.searched != Some(patrol.current) || nav_mesh.is_changed())
+        {
+            patrol.searched = Some(patrol.current);
+            if let Some(path) = nav_mesh.find_path(position.0, waypoint) {
+                patrol.path = path;
+            }
+        }
+
+        // Skip path points that have already been reached
+        let arrival_radius = patrol.arrival_radius;
+        patrol
+            .path
+            .retain(|point| horizontal(*point - position.0).length() > arrival_radius);
+
+        let target = patrol.path.first().copied().unwrap_or(waypoint);
+
+        intent.direction = horizontal(target - position.0).normalize_or_zero();
+        intent.walk = true;
+    }
+}
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use bevy::{animation::RepeatAnimation, gltf::Gltf, prelude::*};
+use bevy_xpbd_3d::{math::*, prelude::*};
+
+use crate::{
+    character::{CharacterController, Grounded},
+    AppState, GameAssets,
+};
+
+/// How long the blend between two animations takes.
+const TRANSITION_DURATION: Duration = Duration::from_millis(200);
+
+/// The horizontal speed above which a grounded character plays the run animation.
+const RUN_SPEED_THRESHOLD: Scalar = 0.2;
+
+/// The upward speed above which an airborne character plays the jump animation.
+const JUMP_SPEED_THRESHOLD: Scalar = 0.1;
+
+pub struct CharacterAnimationPlugin;
+
+impl Plugin for CharacterAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnExit(AppState::Loading), load_character_animations)
+            .add_systems(
+                Update,
+                (link_animation_players, update_animation_state)
+                    .run_if(in_state(AppState::Main))
+                    .chain(),
+            );
+    }
+}
+
+/// The animation clips of the character model, one for each [`AnimationState`].
+/// Clips missing from the model are `None`, and their states are never entered.
+#[derive(Resource)]
+pub struct CharacterAnimations {
+    pub idle: Option<Handle<AnimationClip>>,
+    pub run: Option<Handle<AnimationClip>>,
+    pub jump: Option<Handle<AnimationClip>>,
+    pub fall: Option<Handle<AnimationClip>>,
+    pub land: Option<Handle<AnimationClip>>,
+}
+
+/// The animation a character is currently playing.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AnimationState {
+    #[default]
+    Idle,
+    Run,
+    Jump,
+    Fall,
+    Land,
+}
+
+impl AnimationState {
+    /// The name of the state's animation in the character's glTF file.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Idle => "Idle",
+            Self::Run => "Run",
+            Self::Jump => "Jump",
+            Self::Fall => "Fall",
+            Self::Land => "Land",
+        }
+    }
+
+    fn clip<'a>(&self, animations: &'a CharacterAnimations) -> Option<&'a Handle<AnimationClip>> {
+        match self {
+            Self::Idle => animations.idle.as_ref(),
+            Self::Run => animations.run.as_ref(),
+            Self::Jump => animations.jump.as_ref(),
+            Self::Fall => animations.fall.as_ref(),
+            Self::Land => animations.land.as_ref(),
+        }
+    }
+
+    /// Jump and land are played once, everything else loops.
+    fn repeat(&self) -> RepeatAnimation {
+        match self {
+            Self::Jump | Self::Land => RepeatAnimation::Never,
+            Self::Idle | Self::Run | Self::Fall => RepeatAnimation::Forever,
+        }
+    }
+
+    /// Picks the animation to play from the character's movement.
+    ///
+    /// Landing is kept until its clip has finished, unless the character
+    /// starts running or leaves the ground again.
+    fn next(self, grounded: bool, velocity: Vector, clip_finished: bool) -> Self {
+        let horizontal_speed = Vector::new(velocity.x, 0.0, velocity.z).length();
+
+        if !grounded {
+            return if velocity.y > JUMP_SPEED_THRESHOLD && self != Self::Fall {
+                Self::Jump
+            } else {
+                Self::Fall
+            };
+        }
+
+        match self {
+            Self::Jump | Self::Fall => Self::Land,
+            Self::Land if !clip_finished && horizontal_speed < RUN_SPEED_THRESHOLD => Self::Land,
+            _ if horizontal_speed > RUN_SPEED_THRESHOLD => Self::Run,
+            _ => Self::Idle,
+        }
+    }
+}
+
+/// Looks up the [`CharacterAnimations`] by name, so they don't depend on
+/// the order of the animations in the glTF file. Missing animations are
+/// reported, but don't stop the game.
+fn load_character_animations(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    gltfs: Res<Assets<Gltf>>,
+) {
+    let model = gltfs
+        .get(&game_assets.character_model)
+        .expect("the character model should be loaded with the game assets");
+    let clip = |state: AnimationState| {
+        let clip = model.named_animations.get(state.name()).cloned();
+        if clip.is_none() {
+            warn!("The character model has no {} animation", state.name());
+        }
+        clip
+    };
+
+    commands.insert_resource(CharacterAnimations {
+        idle: clip(AnimationState::Idle),
+        run: clip(AnimationState::Run),
+        jump: clip(AnimationState::Jump),
+        fall: clip(AnimationState::Fall),
+        land: clip(AnimationState::Land),
+    });
+}
+
+/// The [`AnimationPlayer`] inside a character's scene.
+#[derive(Component)]
+struct AnimationPlayerLink(Entity);
+
+/// Links animation players to the character controller their scene belongs to.
+///
+/// The players only exist once the glTF scene has been spawned as a child
+/// of the character, so they are picked up as they are added.
+fn link_animation_players(
+    mut commands: Commands,
+    animations: Res<CharacterAnimations>,
+    mut players: Query<(Entity, &mut AnimationPlayer), Added<AnimationPlayer>>,
+    parents: Query<&Parent>,
+    characters: Query<(), With<CharacterController>>,
+) {
+    for (entity, mut player) in &mut players {
+        let Some(character) = parents
+            .iter_ancestors(entity)
+            .find(|ancestor| characters.contains(*ancestor))
+        else {
+            continue;
+        };
+
+        let state = AnimationState::default();
+        if let Some(clip) = state.clip(&animations) {
+            player.play(clip.clone()).set_repeat(state.repeat());
+        }
+
+        commands
+            .entity(character)
+            .insert((AnimationPlayerLink(entity), state));
+    }
+}
+
+/// Switches each character's animation when its movement changes,
+/// blending from the previous animation. States without a clip are skipped,
+/// so the previous animation keeps playing.
+fn update_animation_state(
+    animations: Res<CharacterAnimations>,
+    mut characters: Query<(
+        &LinearVelocity,
+        Has<Grounded>,
+        &AnimationPlayerLink,
+        &mut AnimationState,
+    )>,
+    mut players: Query<&mut AnimationPlayer>,
+) {
+    for (velocity, grounded, link, mut state) in &mut characters {
+        let Ok(mut player) = players.get_mut(link.0) else {
+            continue;
+        };
+
+        let next = state.next(grounded, velocity.0, player.is_finished());
+        if next == *state {
+            continue;
+        }
+        let Some(clip) = next.clip(&animations) else {
+            continue;
+        };
+
+        player
+            .play_with_transition(clip.clone(), TRANSITION_DURATION)
+            .set_repeat(next.repeat());
+        *state = next;
+    }
+}
@@ -0,0 +1,149 @@
+use std::{fs, path::Path};
+
+use bevy::{prelude::*, utils::HashMap};
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::character::{PlayerAction, PlayerId};
+
+const BINDINGS_PATH: &str = "settings/bindings.ron";
+
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load_or_default(BINDINGS_PATH))
+            .add_systems(Update, apply_bindings);
+    }
+}
+
+/// The input bindings of every [`PlayerAction`], loaded from and saved to a settings file.
+///
+/// The keyboard and mouse bindings are only used by the first player,
+/// while every player that has been assigned a gamepad uses the gamepad bindings with it.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub keyboard_mouse: HashMap<PlayerAction, Vec<UserInput>>,
+    pub gamepad: HashMap<PlayerAction, Vec<UserInput>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let bindings = |default_input: fn(PlayerAction) -> UserInput| {
+            PlayerAction::variants()
+                .map(|action| (action, vec![default_input(action)]))
+                .collect()
+        };
+
+        Self {
+            keyboard_mouse: bindings(PlayerAction::default_keyboard_mouse_input),
+            gamepad: bindings(PlayerAction::default_gamepad_input),
+        }
+    }
+}
+
+impl InputBindings {
+    /// Loads the bindings from a RON file, falling back to the default bindings
+    /// if the file doesn't exist or can't be parsed.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => {
+                info!("No input bindings found at {path:?}, using the defaults");
+                return Self::default();
+            }
+        };
+
+        match ron::from_str::<Self>(&contents) {
+            Ok(mut bindings) => {
+                // Actions missing from the file keep their default bindings
+                let defaults = Self::default();
+                for (action, inputs) in defaults.keyboard_mouse {
+                    bindings.keyboard_mouse.entry(action).or_insert(inputs);
+                }
+                for (action, inputs) in defaults.gamepad {
+                    bindings.gamepad.entry(action).or_insert(inputs);
+                }
+                bindings
+            }
+            Err(error) => {
+                warn!("Invalid input bindings in {path:?}, using the defaults: {error}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the bindings to a RON file, creating its directory if needed.
+    pub fn save(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+
+        let contents = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(contents) => contents,
+            Err(error) => {
+                error!("Failed to serialize input bindings: {error}");
+                return;
+            }
+        };
+
+        if let Some(directory) = path.parent() {
+            if let Err(error) = fs::create_dir_all(directory) {
+                error!("Failed to create {directory:?}: {error}");
+                return;
+            }
+        }
+
+        if let Err(error) = fs::write(path, contents) {
+            error!("Failed to save input bindings to {path:?}: {error}");
+        }
+    }
+
+    /// Saves the bindings to the default settings file.
+    pub fn save_to_settings(&self) {
+        self.save(BINDINGS_PATH);
+    }
+
+    /// Builds the [`InputMap`] for a player.
+    ///
+    /// The gamepad bindings are only added if the player has a gamepad,
+    /// since an input map without one accepts input from any gamepad.
+    pub fn input_map(&self, player: PlayerId, gamepad: Option<Gamepad>) -> InputMap<PlayerAction> {
+        let mut input_map = InputMap::default();
+
+        if player.0 == 0 {
+            for (action, inputs) in &self.keyboard_mouse {
+                for input in inputs {
+                    input_map.insert(input.clone(), *action);
+                }
+            }
+        }
+
+        if let Some(gamepad) = gamepad {
+            for (action, inputs) in &self.gamepad {
+                for input in inputs {
+                    input_map.insert(input.clone(), *action);
+                }
+            }
+            input_map.set_gamepad(gamepad);
+        }
+
+        input_map
+    }
+}
+
+/// Rebuilds the players' input maps when the bindings change,
+/// keeping the gamepads they have been assigned.
+fn apply_bindings(
+    bindings: Res<InputBindings>,
+    mut players: Query<(&PlayerId, &mut InputMap<PlayerAction>)>,
+) {
+    if !bindings.is_changed() || bindings.is_added() {
+        return;
+    }
+
+    for (player, mut input_map) in &mut players {
+        let gamepad = input_map.gamepad();
+        *input_map = bindings.input_map(*player, gamepad);
+    }
+}
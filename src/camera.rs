@@ -1,27 +1,105 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, render::camera::Viewport, window::PrimaryWindow};
 use bevy_third_person_camera::ThirdPersonCamera;
 
+use crate::character::{LocalPlayers, PlayerId};
+
 const CAMERA_DISTANCE: f32 = 2.5;
 
+/// The offset from their player that split-screen cameras follow at.
+const FOLLOW_OFFSET: Vec3 = Vec3::new(0.0, CAMERA_DISTANCE, 5.0);
+
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_camera);
+        app.add_systems(Startup, spawn_camera)
+            .add_systems(Update, (update_split_screen_viewports, follow_players));
     }
 }
 
-fn spawn_camera(mut commands: Commands) {
-    commands.spawn((
-        Camera3dBundle {
-            transform: Transform::from_xyz(-2.0, CAMERA_DISTANCE, 5.0)
-                .looking_at(Vec3::ZERO, Vec3::Y)
-                .looking_at(Vec3::ZERO, Vec3::Y),
-            ..default()
-        },
-        ThirdPersonCamera {
-            mouse_sensitivity: 2.5,
-            ..default()
-        },
-    ));
+/// A camera that stays behind the player with the same [`PlayerId`].
+#[derive(Component)]
+pub struct FollowCamera;
+
+fn spawn_camera(mut commands: Commands, local_players: Res<LocalPlayers>) {
+    for player in 0..local_players.0 {
+        let mut camera = commands.spawn((
+            Camera3dBundle {
+                camera: Camera {
+                    order: player as isize,
+                    ..default()
+                },
+                transform: Transform::from_xyz(-2.0, CAMERA_DISTANCE, 5.0)
+                    .looking_at(Vec3::ZERO, Vec3::Y)
+                    .looking_at(Vec3::ZERO, Vec3::Y),
+                ..default()
+            },
+            PlayerId(player),
+        ));
+
+        // The third person camera plugin only supports a single camera,
+        // so the other players get a simple follow camera instead.
+        if player == 0 {
+            camera.insert(ThirdPersonCamera {
+                mouse_sensitivity: 2.5,
+                ..default()
+            });
+        } else {
+            camera.insert(FollowCamera);
+        }
+    }
+}
+
+/// Splits the window between the players' cameras, side by side
+/// for two players and in a grid for more.
+fn update_split_screen_viewports(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    local_players: Res<LocalPlayers>,
+    mut cameras: Query<(&PlayerId, &mut Camera)>,
+) {
+    if local_players.0 <= 1 {
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    let columns = 2;
+    let rows = (local_players.0 as u32 + 1) / 2;
+    let size = UVec2::new(
+        window.resolution.physical_width() / columns,
+        window.resolution.physical_height() / rows,
+    );
+
+    for (player, mut camera) in &mut cameras {
+        let index = player.0 as u32;
+        let position = UVec2::new(index % columns, index / columns) * size;
+
+        // Only touch the camera when the layout changes to avoid needless change detection
+        let is_up_to_date = camera.viewport.as_ref().is_some_and(|viewport| {
+            viewport.physical_position == position && viewport.physical_size == size
+        });
+
+        if !is_up_to_date {
+            camera.viewport = Some(Viewport {
+                physical_position: position,
+                physical_size: size,
+                ..default()
+            });
+        }
+    }
+}
+
+fn follow_players(
+    players: Query<(&PlayerId, &Transform), Without<Camera>>,
+    mut cameras: Query<(&PlayerId, &mut Transform), (With<Camera>, With<FollowCamera>)>,
+) {
+    for (camera_player, mut transform) in &mut cameras {
+        let Some((_, target)) = players.iter().find(|(player, _)| *player == camera_player) else {
+            continue;
+        };
+
+        *transform = Transform::from_translation(target.translation + FOLLOW_OFFSET)
+            .looking_at(target.translation, Vec3::Y);
+    }
 }
@@ -1,25 +1,63 @@
-use bevy::{ecs::query::Has, prelude::*};
-use bevy_xpbd_3d::{math::*, prelude::*, SubstepSchedule, SubstepSet};
+use std::time::Duration;
+
+use bevy::{
+    ecs::{
+        query::{Has, WorldQuery},
+        system::SystemParam,
+    },
+    input::gamepad::GamepadConnectionEvent,
+    prelude::*,
+};
+use bevy_xpbd_3d::{
+    math::*, prelude::*, PhysicsSchedule, PhysicsStepSet, SubstepSchedule, SubstepSet,
+};
 use leafwing_input_manager::{prelude::*, user_input::InputKind};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bindings::InputBindings,
+    interaction::InteractionRange,
+    inventory::{HandBone, Inventory},
+    ron_asset::RonAssetPlugin,
+    AppState, GameAssets,
+};
 
-use crate::{AppState, GameAssets};
+mod config;
+
+pub use config::CharacterConfig;
 
 const STARTING_TRANSLATION: Vec3 = Vec3::new(0.0, 10.0, 0.0);
 
+/// Extra distance checked in front of the character when looking for steps.
+const STEP_SKIN: Scalar = 0.05;
+
+/// Runs the character controllers. It only depends on physics and [`ActionState`],
+/// so it also works without a window or loaded assets.
 pub struct CharacterControllerPlugin;
 
 impl Plugin for CharacterControllerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(AppState::Main), spawn_character)
+        app.add_systems(Update, sample_player_input.run_if(in_state(AppState::Main)))
+            // The controller runs once per physics tick, right before the simulation step,
+            // so its behavior doesn't depend on the frame rate.
             .add_systems(
-                Update,
+                PhysicsSchedule,
                 (
                     update_grounded,
                     apply_deferred,
+                    move_with_platforms,
+                    reset_air_jumps,
+                    update_movement_mode,
                     apply_gravity,
-                    player_actions,
+                    apply_slope_sliding,
+                    // Damping comes before input, so characters reach exactly their max speed
                     apply_movement_damping,
+                    player_actions,
+                    climb_steps,
+                    push_dynamic_bodies,
                 )
+                    .in_set(ControllerSet)
+                    .before(PhysicsStepSet::BroadPhase)
                     .run_if(in_state(AppState::Main))
                     .chain(),
             )
@@ -30,24 +68,55 @@ impl Plugin for CharacterControllerPlugin {
     }
 }
 
-#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect)]
+/// The character controller systems in the [`PhysicsSchedule`], for systems
+/// that need to run before or after the controllers on every physics tick.
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ControllerSet;
+
+/// Spawns a character for every local player and hands out gamepads to them.
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<CharacterConfig>::new(&["character.ron"]))
+            .init_resource::<LocalPlayers>()
+            // Players are kept across levels, so they are only spawned once
+            .add_systems(OnExit(AppState::Loading), spawn_character)
+            .add_systems(
+                Update,
+                (assign_gamepads, config::apply_character_config).run_if(in_state(AppState::Main)),
+            );
+    }
+}
+
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Reflect, Serialize, Deserialize)]
 pub enum PlayerAction {
     Run,
     Jump,
     UseItem,
+    Sprint,
+    Crouch,
+    Walk,
+    NextItem,
+    PreviousItem,
 }
 
 impl PlayerAction {
-    fn default_keyboard_mouse_input(action: PlayerAction) -> UserInput {
+    pub fn default_keyboard_mouse_input(action: PlayerAction) -> UserInput {
         // Match against the provided action to get the correct default keyboard-mouse input
         match action {
             Self::Run => UserInput::VirtualDPad(VirtualDPad::wasd()),
             Self::Jump => UserInput::Single(InputKind::Keyboard(KeyCode::Space)),
             Self::UseItem => UserInput::Single(InputKind::Mouse(MouseButton::Left)),
+            Self::Sprint => UserInput::Single(InputKind::Keyboard(KeyCode::ShiftLeft)),
+            Self::Crouch => UserInput::Single(InputKind::Keyboard(KeyCode::ControlLeft)),
+            Self::Walk => UserInput::Single(InputKind::Keyboard(KeyCode::AltLeft)),
+            Self::NextItem => UserInput::Single(InputKind::Keyboard(KeyCode::E)),
+            Self::PreviousItem => UserInput::Single(InputKind::Keyboard(KeyCode::Q)),
         }
     }
 
-    fn default_gamepad_input(action: PlayerAction) -> UserInput {
+    pub fn default_gamepad_input(action: PlayerAction) -> UserInput {
         // Match against the provided action to get the correct default gamepad input
         match action {
             Self::Run => UserInput::Single(InputKind::DualAxis(DualAxis::left_stick())),
@@ -55,23 +124,167 @@ impl PlayerAction {
             Self::UseItem => {
                 UserInput::Single(InputKind::GamepadButton(GamepadButtonType::RightTrigger2))
             }
+            Self::Sprint => {
+                UserInput::Single(InputKind::GamepadButton(GamepadButtonType::LeftThumb))
+            }
+            Self::Crouch => UserInput::Single(InputKind::GamepadButton(GamepadButtonType::East)),
+            Self::Walk => {
+                UserInput::Single(InputKind::GamepadButton(GamepadButtonType::LeftTrigger))
+            }
+            Self::NextItem => {
+                UserInput::Single(InputKind::GamepadButton(GamepadButtonType::DPadRight))
+            }
+            Self::PreviousItem => {
+                UserInput::Single(InputKind::GamepadButton(GamepadButtonType::DPadLeft))
+            }
+        }
+    }
+}
+
+/// The number of local players to spawn characters and cameras for.
+#[derive(Resource)]
+pub struct LocalPlayers(pub usize);
+
+impl Default for LocalPlayers {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+impl LocalPlayers {
+    /// Reads the number of players from a `--players <count>` command line argument,
+    /// e.g. `--players 2` for split-screen. Defaults to a single player.
+    pub fn from_args() -> Self {
+        let count = std::env::args().skip_while(|arg| arg != "--players").nth(1);
+
+        match count.map(|count| count.parse::<usize>()) {
+            Some(Ok(count)) if count > 0 => Self(count),
+            Some(_) => {
+                warn!("--players expects a positive number of players, using a single player");
+                Self::default()
+            }
+            None => Self::default(),
         }
     }
 }
 
+/// Identifies which local player a character or camera belongs to.
+/// Player `0` is also controlled with the keyboard and mouse.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PlayerId(pub usize);
+
 /// A marker component indicating that an entity is using a character controller.
 #[derive(Component)]
 pub struct CharacterController;
 
+/// What a character controller is trying to do, consumed by the controller
+/// on the next physics tick.
+///
+/// Players write their sampled input here once per frame, and AI behaviors
+/// write their decisions, so both drive the controller the same way.
+///
+/// Button presses are latched until a tick has seen them, so they aren't lost
+/// on frames without a physics step or repeated on frames with several.
+#[derive(Component, Default)]
+pub struct MovementIntent {
+    /// The desired horizontal movement direction in world space.
+    pub direction: Vector,
+    pub jump_pressed: bool,
+    pub jump_released: bool,
+    pub sprint: bool,
+    pub crouch: bool,
+    pub walk: bool,
+}
+
+/// The way a character controller is currently moving.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MovementMode {
+    #[default]
+    Run,
+    Walk,
+    Sprint,
+    Crouch,
+}
+
+/// Multipliers applied to [`MovementAcceleration`] and [`MaxSpeed`] in each [`MovementMode`].
+/// Running uses the unmodified values.
+#[derive(Component)]
+pub struct MovementModeSpeeds {
+    pub walk: Scalar,
+    pub sprint: Scalar,
+    pub crouch: Scalar,
+}
+
+impl MovementModeSpeeds {
+    fn multiplier(&self, mode: MovementMode) -> Scalar {
+        match mode {
+            MovementMode::Run => 1.0,
+            MovementMode::Walk => self.walk,
+            MovementMode::Sprint => self.sprint,
+            MovementMode::Crouch => self.crouch,
+        }
+    }
+}
+
+impl Default for MovementModeSpeeds {
+    fn default() -> Self {
+        Self {
+            walk: 0.4,
+            sprint: 1.6,
+            crouch: 0.5,
+        }
+    }
+}
+
+/// The colliders a character switches between when crouching and standing up.
+///
+/// The character's position is shifted by half of the height difference
+/// so that its feet stay on the ground when the collider changes.
+#[derive(Component)]
+pub struct CrouchShape {
+    standing: Collider,
+    crouching: Collider,
+    height_offset: Scalar,
+}
+
+impl CrouchShape {
+    fn new(standing: Collider, crouching: Collider) -> Self {
+        let standing_height = standing.shape().compute_local_aabb().extents().y;
+        let crouching_height = crouching.shape().compute_local_aabb().extents().y;
+
+        Self {
+            standing,
+            crouching,
+            height_offset: (standing_height - crouching_height) * 0.5,
+        }
+    }
+}
+
 /// A marker component indicating that an entity is on the ground.
 #[derive(Component)]
 #[component(storage = "SparseSet")]
 pub struct Grounded;
+/// The rigid body a grounded character is standing on.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct GroundedOn(pub Entity);
+
+/// A marker for characters that have jumped off the ground and are still rising.
+///
+/// The ground caster still reaches the ground for a few ticks after a jump,
+/// so it is ignored until the character is airborne or starts falling.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct Jumping;
+
 /// The acceleration used for character movement.
 #[derive(Component)]
 pub struct MovementAcceleration(Scalar);
 
-/// The damping factor used for slowing down movement.
+/// The rate at which horizontal movement slows down, per second.
+///
+/// Velocity decays exponentially, so after `t` seconds without input
+/// `e^(-factor * t)` of the horizontal velocity is left regardless of frame rate.
 #[derive(Component)]
 pub struct MovementDampingFactor(Scalar);
 
@@ -79,6 +292,74 @@ pub struct MovementDampingFactor(Scalar);
 #[derive(Component)]
 pub struct JumpImpulse(Scalar);
 
+/// The top horizontal speed that movement input can accelerate the character to.
+#[derive(Component)]
+pub struct MaxSpeed(Scalar);
+
+/// The fraction of [`MovementAcceleration`] available while airborne.
+#[derive(Component)]
+pub struct AirControl(Scalar);
+
+/// How quickly the character turns to face its movement direction.
+/// Higher values turn faster.
+#[derive(Component)]
+pub struct TurnSpeed(Scalar);
+
+/// The fraction of upward velocity kept when the jump button is released
+/// while still rising. Lower values give shorter hops, `1.0` disables the cut.
+#[derive(Component)]
+pub struct JumpCutMultiplier(Scalar);
+
+/// The number of extra jumps a character can perform while airborne.
+#[derive(Component)]
+pub struct MaxAirJumps(u32);
+
+/// The number of air jumps left before the character has to land again.
+#[derive(Component)]
+pub struct AirJumpsRemaining(u32);
+
+/// The grace period after walking off a ledge during which a jump is still allowed.
+#[derive(Component)]
+pub struct CoyoteTime(Timer);
+
+impl CoyoteTime {
+    pub fn new(seconds: f32) -> Self {
+        Self(Timer::from_seconds(seconds, TimerMode::Once))
+    }
+
+    fn is_active(&self) -> bool {
+        !self.0.finished()
+    }
+
+    fn consume(&mut self) {
+        let remaining = self.0.remaining();
+        self.0.tick(remaining);
+    }
+}
+
+/// How long a jump press is remembered before the character lands,
+/// so pressing jump slightly too early still results in a jump.
+#[derive(Component)]
+pub struct JumpBuffer(Timer);
+
+impl JumpBuffer {
+    pub fn new(seconds: f32) -> Self {
+        let mut timer = Timer::from_seconds(seconds, TimerMode::Once);
+        // Start out expired so the character doesn't jump on spawn
+        timer.tick(timer.duration());
+        Self(timer)
+    }
+
+    fn is_active(&self) -> bool {
+        !self.0.finished()
+    }
+
+    fn consume(&mut self) {
+        let remaining = self.0.remaining();
+        self.0.tick(remaining);
+    }
+}
+
 /// The gravitational acceleration used for a character controller.
 #[derive(Component)]
 pub struct ControllerGravity(Vector);
@@ -89,13 +370,122 @@ pub struct ControllerGravity(Vector);
 #[derive(Component)]
 pub struct MaxSlopeAngle(Scalar);
 
+/// The acceleration pulling a character down slopes that are steeper than its [`MaxSlopeAngle`].
+#[derive(Component)]
+pub struct SlopeSlideAcceleration(Scalar);
+
+/// The maximum height of a ledge or stair the character can step onto without jumping.
+/// Grounded characters are also snapped down by up to this height when walking down stairs.
+#[derive(Component)]
+pub struct MaxStepHeight(Scalar);
+
+/// The force a kinematic character applies to dynamic rigid bodies it walks into.
+#[derive(Component)]
+pub struct PushForce(Scalar);
+
+/// The state of a character controller that carries over from one physics tick
+/// to the next, for putting a character back to an earlier tick.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct CharacterState {
+    pub position: Vector,
+    pub rotation: Quaternion,
+    pub linear_velocity: Vector,
+    pub mode: MovementMode,
+    /// The elapsed time of the [`CoyoteTime`] window, in seconds.
+    pub coyote_time: f32,
+    /// The elapsed time of the [`JumpBuffer`] window, in seconds.
+    pub jump_buffer: f32,
+    pub air_jumps_remaining: u32,
+    /// The body the character stands on, if it's [`Grounded`].
+    pub grounded_on: Option<Entity>,
+    pub jumping: bool,
+}
+
+/// Reads and restores the [`CharacterState`] of a character controller.
+#[derive(WorldQuery)]
+#[world_query(mutable)]
+pub struct CharacterStateQuery {
+    entity: Entity,
+    position: &'static mut Position,
+    rotation: &'static mut Rotation,
+    linear_velocity: &'static mut LinearVelocity,
+    mode: &'static mut MovementMode,
+    crouch_shape: &'static CrouchShape,
+    collider: &'static mut Collider,
+    shape_caster: &'static mut ShapeCaster,
+    coyote_time: &'static mut CoyoteTime,
+    jump_buffer: &'static mut JumpBuffer,
+    air_jumps_remaining: &'static mut AirJumpsRemaining,
+    grounded_on: Option<&'static GroundedOn>,
+    jumping: Has<Jumping>,
+}
+
+impl CharacterStateQueryItem<'_> {
+    pub fn state(&self) -> CharacterState {
+        CharacterState {
+            position: self.position.0,
+            rotation: self.rotation.0,
+            linear_velocity: self.linear_velocity.0,
+            mode: *self.mode,
+            coyote_time: self.coyote_time.0.elapsed_secs(),
+            jump_buffer: self.jump_buffer.0.elapsed_secs(),
+            air_jumps_remaining: self.air_jumps_remaining.0,
+            grounded_on: self.grounded_on.map(|grounded_on| grounded_on.0),
+            jumping: self.jumping,
+        }
+    }
+
+    /// Puts the character back into the given state, swapping its collider
+    /// if the state has a different [`MovementMode`].
+    ///
+    /// The ground caster isn't updated until the next spatial query step,
+    /// so this should run after the simulation step and before the spatial queries.
+    pub fn restore(&mut self, state: &CharacterState, commands: &mut Commands) {
+        self.position.0 = state.position;
+        self.rotation.0 = state.rotation;
+        self.linear_velocity.0 = state.linear_velocity;
+
+        *self.mode = state.mode;
+        *self.collider = if state.mode == MovementMode::Crouch {
+            self.crouch_shape.crouching.clone()
+        } else {
+            self.crouch_shape.standing.clone()
+        };
+        self.shape_caster.shape = caster_shape(&self.collider);
+
+        // Ticking the timers also updates whether they have finished
+        self.coyote_time.0.reset();
+        self.coyote_time
+            .0
+            .tick(Duration::from_secs_f32(state.coyote_time));
+        self.jump_buffer.0.reset();
+        self.jump_buffer
+            .0
+            .tick(Duration::from_secs_f32(state.jump_buffer));
+        self.air_jumps_remaining.0 = state.air_jumps_remaining;
+
+        let mut entity = commands.entity(self.entity);
+        match state.grounded_on {
+            Some(ground) => entity.insert((Grounded, GroundedOn(ground))),
+            None => entity.remove::<(Grounded, GroundedOn)>(),
+        };
+        if state.jumping {
+            entity.insert(Jumping);
+        } else {
+            entity.remove::<Jumping>();
+        }
+    }
+}
+
 /// A bundle that contains the components needed for a basic
 /// kinematic character controller.
 #[derive(Bundle)]
 pub struct CharacterControllerBundle {
     character_controller: CharacterController,
+    intent: MovementIntent,
     rigid_body: RigidBody,
     collider: Collider,
+    crouch_shape: CrouchShape,
     ground_caster: ShapeCaster,
     gravity: ControllerGravity,
     movement: MovementBundle,
@@ -106,12 +496,25 @@ pub struct CharacterControllerBundle {
 pub struct MovementBundle {
     acceleration: MovementAcceleration,
     damping: MovementDampingFactor,
+    mode: MovementMode,
+    mode_speeds: MovementModeSpeeds,
+    max_speed: MaxSpeed,
+    air_control: AirControl,
+    turn_speed: TurnSpeed,
     jump_impulse: JumpImpulse,
+    coyote_time: CoyoteTime,
+    jump_buffer: JumpBuffer,
+    jump_cut: JumpCutMultiplier,
+    max_air_jumps: MaxAirJumps,
+    air_jumps_remaining: AirJumpsRemaining,
     max_slope_angle: MaxSlopeAngle,
+    slope_slide_acceleration: SlopeSlideAcceleration,
+    max_step_height: MaxStepHeight,
+    push_force: PushForce,
 }
 
 impl MovementBundle {
-    pub const fn new(
+    pub fn new(
         acceleration: Scalar,
         damping: Scalar,
         jump_impulse: Scalar,
@@ -120,40 +523,162 @@ impl MovementBundle {
         Self {
             acceleration: MovementAcceleration(acceleration),
             damping: MovementDampingFactor(damping),
+            mode: MovementMode::default(),
+            mode_speeds: MovementModeSpeeds::default(),
+            max_speed: MaxSpeed(4.5),
+            air_control: AirControl(0.5),
+            turn_speed: TurnSpeed(10.0),
             jump_impulse: JumpImpulse(jump_impulse),
+            coyote_time: CoyoteTime::new(0.1),
+            jump_buffer: JumpBuffer::new(0.1),
+            jump_cut: JumpCutMultiplier(0.5),
+            max_air_jumps: MaxAirJumps(0),
+            air_jumps_remaining: AirJumpsRemaining(0),
             max_slope_angle: MaxSlopeAngle(max_slope_angle),
+            slope_slide_acceleration: SlopeSlideAcceleration(15.0),
+            max_step_height: MaxStepHeight(0.25),
+            push_force: PushForce(50.0),
         }
     }
+
+    /// Sets the acceleration, damping, jump impulse and max slope angle,
+    /// keeping the rest of the tuning.
+    pub fn with_movement(
+        mut self,
+        acceleration: Scalar,
+        damping: Scalar,
+        jump_impulse: Scalar,
+        max_slope_angle: Scalar,
+    ) -> Self {
+        self.acceleration = MovementAcceleration(acceleration);
+        self.damping = MovementDampingFactor(damping);
+        self.jump_impulse = JumpImpulse(jump_impulse);
+        self.max_slope_angle = MaxSlopeAngle(max_slope_angle);
+        self
+    }
+
+    /// Sets the force the character pushes dynamic rigid bodies with.
+    pub fn with_push_force(mut self, force: Scalar) -> Self {
+        self.push_force = PushForce(force);
+        self
+    }
+
+    /// Sets the highest step the character can walk onto and snap down from.
+    pub fn with_max_step_height(mut self, height: Scalar) -> Self {
+        self.max_step_height = MaxStepHeight(height);
+        self
+    }
+
+    /// Sets how fast the character accelerates down slopes that are too steep to stand on.
+    pub fn with_slope_slide_acceleration(mut self, acceleration: Scalar) -> Self {
+        self.slope_slide_acceleration = SlopeSlideAcceleration(acceleration);
+        self
+    }
+
+    /// Sets the speed multipliers for walking, sprinting and crouching.
+    pub fn with_mode_speeds(mut self, walk: Scalar, sprint: Scalar, crouch: Scalar) -> Self {
+        self.mode_speeds = MovementModeSpeeds {
+            walk,
+            sprint,
+            crouch,
+        };
+        self
+    }
+
+    /// Sets the top horizontal speed and the fraction of acceleration available in the air.
+    pub fn with_speed_limits(mut self, max_speed: Scalar, air_control: Scalar) -> Self {
+        self.max_speed = MaxSpeed(max_speed);
+        self.air_control = AirControl(air_control);
+        self
+    }
+
+    /// Sets how quickly the character turns towards its movement direction.
+    pub fn with_turn_speed(mut self, turn_speed: Scalar) -> Self {
+        self.turn_speed = TurnSpeed(turn_speed);
+        self
+    }
+
+    /// Sets the coyote time and jump buffer windows in seconds.
+    pub fn with_jump_windows(mut self, coyote_time: f32, jump_buffer: f32) -> Self {
+        self.coyote_time = CoyoteTime::new(coyote_time);
+        self.jump_buffer = JumpBuffer::new(jump_buffer);
+        self
+    }
+
+    /// Sets how much upward velocity is kept when the jump button is released early.
+    pub fn with_jump_cut(mut self, multiplier: Scalar) -> Self {
+        self.jump_cut = JumpCutMultiplier(multiplier);
+        self
+    }
+
+    /// Sets how many extra jumps the character can perform before landing.
+    pub fn with_air_jumps(mut self, max_air_jumps: u32) -> Self {
+        self.max_air_jumps = MaxAirJumps(max_air_jumps);
+        self.air_jumps_remaining = AirJumpsRemaining(max_air_jumps);
+        self
+    }
 }
 
 impl Default for MovementBundle {
     fn default() -> Self {
-        Self::new(30.0, 0.9, 7.0, PI * 0.45)
+        Self::new(30.0, 6.0, 7.0, PI * 0.45)
     }
 }
 
 impl CharacterControllerBundle {
     pub fn new(collider: Collider, gravity: Vector) -> Self {
-        // Create shape caster as a slightly smaller version of collider
-        let mut caster_shape = collider.clone();
-        caster_shape.set_scale(Vector::ONE * 0.99, 10);
-
         Self {
             character_controller: CharacterController,
+            intent: MovementIntent::default(),
             rigid_body: RigidBody::Kinematic,
-            collider,
+            crouch_shape: CrouchShape::new(collider.clone(), collider.clone()),
             ground_caster: ShapeCaster::new(
-                caster_shape,
+                caster_shape(&collider),
                 Vector::ZERO,
                 Quaternion::default(),
                 Vector::NEG_Y,
             )
-            .with_max_time_of_impact(0.2),
+            .with_max_time_of_impact(0.2)
+            // Sensors below the character are skipped, so the ground can be a later hit
+            .with_max_hits(4),
+            collider,
             gravity: ControllerGravity(gravity),
             movement: MovementBundle::default(),
         }
     }
 
+    /// Sets the collider used while crouching.
+    pub fn with_crouch_collider(mut self, crouching: Collider) -> Self {
+        self.crouch_shape = CrouchShape::new(self.collider.clone(), crouching);
+        self
+    }
+
+    /// Sets the force the character pushes dynamic rigid bodies with.
+    pub fn with_push_force(mut self, force: Scalar) -> Self {
+        self.movement = self.movement.with_push_force(force);
+        self
+    }
+
+    /// Sets the highest step the character can walk onto and snap down from.
+    pub fn with_max_step_height(mut self, height: Scalar) -> Self {
+        self.movement = self.movement.with_max_step_height(height);
+        self
+    }
+
+    /// Sets how fast the character accelerates down slopes that are too steep to stand on.
+    pub fn with_slope_slide_acceleration(mut self, acceleration: Scalar) -> Self {
+        self.movement = self.movement.with_slope_slide_acceleration(acceleration);
+        self
+    }
+
+    /// Sets the speed multipliers for walking, sprinting and crouching.
+    pub fn with_mode_speeds(mut self, walk: Scalar, sprint: Scalar, crouch: Scalar) -> Self {
+        self.movement = self.movement.with_mode_speeds(walk, sprint, crouch);
+        self
+    }
+
+    /// Sets the acceleration, damping, jump impulse and max slope angle,
+    /// keeping the rest of the tuning.
     pub fn with_movement(
         mut self,
         acceleration: Scalar,
@@ -161,124 +686,839 @@ impl CharacterControllerBundle {
         jump_impulse: Scalar,
         max_slope_angle: Scalar,
     ) -> Self {
-        self.movement = MovementBundle::new(acceleration, damping, jump_impulse, max_slope_angle);
+        self.movement =
+            self.movement
+                .with_movement(acceleration, damping, jump_impulse, max_slope_angle);
         self
     }
+
+    /// Sets the top horizontal speed and the fraction of acceleration available in the air.
+    pub fn with_speed_limits(mut self, max_speed: Scalar, air_control: Scalar) -> Self {
+        self.movement = self.movement.with_speed_limits(max_speed, air_control);
+        self
+    }
+
+    /// Sets how quickly the character turns towards its movement direction.
+    pub fn with_turn_speed(mut self, turn_speed: Scalar) -> Self {
+        self.movement = self.movement.with_turn_speed(turn_speed);
+        self
+    }
+
+    /// Sets the coyote time and jump buffer windows in seconds.
+    pub fn with_jump_windows(mut self, coyote_time: f32, jump_buffer: f32) -> Self {
+        self.movement = self.movement.with_jump_windows(coyote_time, jump_buffer);
+        self
+    }
+
+    /// Sets how much upward velocity is kept when the jump button is released early.
+    pub fn with_jump_cut(mut self, multiplier: Scalar) -> Self {
+        self.movement = self.movement.with_jump_cut(multiplier);
+        self
+    }
+
+    /// Sets how many extra jumps the character can perform before landing.
+    pub fn with_air_jumps(mut self, max_air_jumps: u32) -> Self {
+        self.movement = self.movement.with_air_jumps(max_air_jumps);
+        self
+    }
+}
+
+/// Creates the ground caster shape as a slightly smaller version of the collider.
+fn caster_shape(collider: &Collider) -> Collider {
+    let mut caster_shape = collider.clone();
+    caster_shape.set_scale(Vector::ONE * 0.99, 10);
+    caster_shape
+}
+
+/// A [`SpatialQuery`] that ignores [`Sensor`] colliders, since they don't block characters
+/// or hold them up.
+#[derive(SystemParam)]
+struct SolidSpatialQuery<'w, 's> {
+    spatial_query: SpatialQuery<'w, 's>,
+    sensors: Query<'w, 's, (), With<Sensor>>,
+}
+
+impl SolidSpatialQuery<'_, '_> {
+    fn is_solid(&self, entity: Entity) -> bool {
+        !self.sensors.contains(entity)
+    }
+
+    /// Like [`SpatialQuery::cast_shape`], returning the first solid hit.
+    fn cast_shape(
+        &self,
+        shape: &Collider,
+        origin: Vector,
+        rotation: Quaternion,
+        direction: Vector,
+        max_time_of_impact: Scalar,
+        query_filter: SpatialQueryFilter,
+    ) -> Option<ShapeHitData> {
+        // Hits are reported in order of their time of impact
+        let mut first_hit = None;
+        self.spatial_query.shape_hits_callback(
+            shape,
+            origin,
+            rotation,
+            direction,
+            max_time_of_impact,
+            true,
+            query_filter,
+            |hit| {
+                if !self.is_solid(hit.entity) {
+                    return true;
+                }
+                first_hit = Some(hit);
+                false
+            },
+        );
+        first_hit
+    }
+
+    /// Checks if a shape overlaps any solid collider.
+    fn intersects_shape(
+        &self,
+        shape: &Collider,
+        position: Vector,
+        rotation: Quaternion,
+        query_filter: SpatialQueryFilter,
+    ) -> bool {
+        self.spatial_query
+            .shape_intersections(shape, position, rotation, query_filter)
+            .into_iter()
+            .any(|entity| self.is_solid(entity))
+    }
+}
+
+fn spawn_character(
+    mut commands: Commands,
+    scene_assets: Res<GameAssets>,
+    configs: Res<Assets<CharacterConfig>>,
+    local_players: Res<LocalPlayers>,
+    bindings: Res<InputBindings>,
+    gamepads: Res<Gamepads>,
+) {
+    let config = configs
+        .get(&scene_assets.character_config)
+        .cloned()
+        .unwrap_or_default();
+    let mut gamepads = gamepads.iter();
+
+    for player in 0..local_players.0 {
+        // Players without a gamepad get one assigned when it's connected
+        let input_map = bindings.input_map(PlayerId(player), gamepads.next());
+
+        commands.spawn((
+            SceneBundle {
+                scene: scene_assets.character.clone(),
+                transform: Transform::from_translation(
+                    STARTING_TRANSLATION + Vec3::X * 2.0 * player as f32,
+                ),
+                ..default()
+            },
+            PlayerId(player),
+            InteractionRange(1.5),
+            Inventory::new(5),
+            HandBone("Hand.R".to_string()),
+            config.controller_bundle(),
+            scene_assets.character_config.clone(),
+            InputManagerBundle::<PlayerAction> {
+                input_map,
+                ..default()
+            },
+            DebugRender::default().with_collider_color(Color::RED),
+        ));
+    }
+}
+
+/// Gives newly connected gamepads to the first player without one,
+/// and frees up the gamepads of players that get disconnected.
+///
+/// The players' input maps are rebuilt, so only players with a gamepad
+/// have gamepad bindings.
+fn assign_gamepads(
+    mut connection_events: EventReader<GamepadConnectionEvent>,
+    bindings: Res<InputBindings>,
+    mut players: Query<(&PlayerId, &mut InputMap<PlayerAction>)>,
+) {
+    for event in connection_events.read() {
+        if event.connected() {
+            if players
+                .iter()
+                .any(|(_, input_map)| input_map.gamepad() == Some(event.gamepad))
+            {
+                continue;
+            }
+
+            if let Some((player, mut input_map)) = players
+                .iter_mut()
+                .filter(|(_, input_map)| input_map.gamepad().is_none())
+                .min_by_key(|(player, _)| player.0)
+            {
+                *input_map = bindings.input_map(*player, Some(event.gamepad));
+            }
+        } else {
+            for (player, mut input_map) in &mut players {
+                if input_map.gamepad() == Some(event.gamepad) {
+                    *input_map = bindings.input_map(*player, None);
+                }
+            }
+        }
+    }
 }
 
-fn spawn_character(mut commands: Commands, scene_assets: Res<GameAssets>) {
-    let mut input_map = InputMap::default();
+/// Converts a 2D movement input into a horizontal world-space direction.
+///
+/// The input's Y axis points away from the camera and its X axis to the camera's right,
+/// both flattened onto the ground plane. Without a camera, forward is world-space -Z.
+fn movement_direction(input: Vec2, camera: Option<&GlobalTransform>) -> Vector {
+    let (forward, right) = camera
+        .map(|camera| {
+            let forward = (camera.forward() * Vec3::new(1.0, 0.0, 1.0)).normalize_or_zero();
+            let right = (camera.right() * Vec3::new(1.0, 0.0, 1.0)).normalize_or_zero();
+            (forward, right)
+        })
+        .filter(|(forward, right)| *forward != Vec3::ZERO && *right != Vec3::ZERO)
+        .unwrap_or((Vec3::NEG_Z, Vec3::X));
 
-    for action in PlayerAction::variants() {
-        input_map.insert(PlayerAction::default_keyboard_mouse_input(action), action);
-        input_map.insert(PlayerAction::default_gamepad_input(action), action);
+    (right * input.x + forward * input.y).clamp_length_max(1.0)
+}
+
+/// Samples each player's [`ActionState`] into their [`MovementIntent`],
+/// relative to the camera of the same player.
+fn sample_player_input(
+    camera_q: Query<(&PlayerId, &GlobalTransform), With<Camera>>,
+    mut query: Query<(&PlayerId, &ActionState<PlayerAction>, &mut MovementIntent)>,
+) {
+    for (player, action_state, mut intent) in &mut query {
+        let camera = camera_q
+            .iter()
+            .find(|(camera_player, _)| *camera_player == player)
+            .map(|(_, transform)| transform);
+
+        intent.direction = if action_state.pressed(PlayerAction::Run) {
+            // Run can be rebound to a button, which has no axis to move along
+            let axis = action_state
+                .clamped_axis_pair(PlayerAction::Run)
+                .map_or(Vec2::ZERO, |axis| axis.xy());
+            movement_direction(axis, camera)
+        } else {
+            Vector::ZERO
+        };
+
+        intent.jump_pressed |= action_state.just_pressed(PlayerAction::Jump);
+        intent.jump_released |= action_state.just_released(PlayerAction::Jump);
+        intent.sprint = action_state.pressed(PlayerAction::Sprint);
+        intent.crouch = action_state.pressed(PlayerAction::Crouch);
+        intent.walk = action_state.pressed(PlayerAction::Walk);
     }
+}
+
+/// Picks the [`MovementMode`] from the held inputs and swaps the collider
+/// and ground caster shapes when crouching or standing up.
+///
+/// A crouching character only stands up if the standing collider
+/// wouldn't overlap any geometry above it.
+#[allow(clippy::type_complexity)]
+fn update_movement_mode(
+    spatial_query: SolidSpatialQuery,
+    mut query: Query<(
+        Entity,
+        &MovementIntent,
+        &CrouchShape,
+        &mut MovementMode,
+        &mut Collider,
+        &mut ShapeCaster,
+        &mut Position,
+        &Rotation,
+    )>,
+) {
+    for (
+        entity,
+        intent,
+        crouch_shape,
+        mut mode,
+        mut collider,
+        mut shape_caster,
+        mut position,
+        rotation,
+    ) in &mut query
+    {
+        let is_crouching = *mode == MovementMode::Crouch;
+
+        let wants_to_stand = is_crouching && !intent.crouch;
+        let can_stand = !wants_to_stand || {
+            // Test a slightly smaller standing collider so touching the floor doesn't count
+            let mut test_shape = crouch_shape.standing.clone();
+            test_shape.set_scale(Vector::ONE * 0.95, 10);
+
+            !spatial_query.intersects_shape(
+                &test_shape,
+                position.0 + Vector::Y * crouch_shape.height_offset,
+                rotation.0,
+                SpatialQueryFilter::default().without_entities([entity]),
+            )
+        };
+
+        let new_mode = if intent.crouch || (is_crouching && !can_stand) {
+            MovementMode::Crouch
+        } else if intent.sprint {
+            MovementMode::Sprint
+        } else if intent.walk {
+            MovementMode::Walk
+        } else {
+            MovementMode::Run
+        };
+
+        if new_mode == *mode {
+            continue;
+        }
+
+        // Swap the shapes while keeping the character's feet in place
+        if new_mode == MovementMode::Crouch {
+            *collider = crouch_shape.crouching.clone();
+            shape_caster.shape = caster_shape(&collider);
+            position.0 -= Vector::Y * crouch_shape.height_offset;
+        } else if is_crouching {
+            *collider = crouch_shape.standing.clone();
+            shape_caster.shape = caster_shape(&collider);
+            position.0 += Vector::Y * crouch_shape.height_offset;
+        }
 
-    commands.spawn((
-        SceneBundle {
-            scene: scene_assets.character.clone(),
-            transform: Transform::from_translation(STARTING_TRANSLATION),
-            ..default()
-        },
-        CharacterControllerBundle::new(Collider::capsule(1.25, 0.2), Vector::NEG_Y * 9.81 * 2.0)
-            .with_movement(30.0, 0.92, 7.0, (30.0 as Scalar).to_radians()),
-        InputManagerBundle::<PlayerAction> {
-            input_map,
-            ..default()
-        },
-        DebugRender::default().with_collider_color(Color::RED),
-    ));
+        *mode = new_mode;
+    }
 }
 
 fn player_actions(
-    time: Res<Time>,
-    action_q: Query<&ActionState<PlayerAction>, With<CharacterController>>,
+    mut commands: Commands,
+    delta_time: Res<DeltaTime>,
     mut controllers: Query<(
+        Entity,
+        &mut MovementIntent,
         &MovementAcceleration,
+        Option<(&MovementMode, &MovementModeSpeeds)>,
+        Option<&MaxSpeed>,
+        Option<&AirControl>,
         &JumpImpulse,
         &mut LinearVelocity,
-        &mut Transform,
+        &mut Rotation,
+        Option<&TurnSpeed>,
+        Option<&mut CoyoteTime>,
+        Option<&mut JumpBuffer>,
+        Option<&JumpCutMultiplier>,
+        Option<&mut AirJumpsRemaining>,
         Has<Grounded>,
     )>,
 ) {
-    let action_state = action_q.single();
-
-    for (movement_acceleration, jump_impulse, mut linear_velocity, mut transform, is_grounded) in
-        &mut controllers
+    for (
+        entity,
+        mut intent,
+        movement_acceleration,
+        mode,
+        max_speed,
+        air_control,
+        jump_impulse,
+        mut linear_velocity,
+        mut rotation,
+        turn_speed,
+        mut coyote_time,
+        mut jump_buffer,
+        jump_cut,
+        mut air_jumps,
+        is_grounded,
+    ) in &mut controllers
     {
-        if action_state.pressed(PlayerAction::Run) {
-            let direction = action_state
-                .clamped_axis_pair(PlayerAction::Run)
-                .unwrap()
-                .xy();
+        let direction = intent.direction;
 
-            let rotation_angle = if direction.length() > 0.0 {
-                direction.x.atan2(direction.y)
-            } else {
-                0.0
+        // Turn towards the movement direction, keeping the last facing when there's no input
+        if direction.length() > 0.0 {
+            let target = Quaternion::from_rotation_y((-direction.x).atan2(-direction.z));
+
+            rotation.0 = match turn_speed {
+                Some(turn_speed) => rotation
+                    .0
+                    .slerp(target, (turn_speed.0 * delta_time.0).min(1.0)),
+                None => target,
             };
 
-            transform.rotation = Quat::from_rotation_y(-rotation_angle as f32);
+            let speed_multiplier =
+                mode.map_or(1.0, |(mode, mode_speeds)| mode_speeds.multiplier(*mode));
 
-            if is_grounded {
-                linear_velocity.x += direction.x * movement_acceleration.0 * time.delta_seconds();
-                linear_velocity.z -= direction.y * movement_acceleration.0 * time.delta_seconds();
+            let acceleration = if is_grounded {
+                movement_acceleration.0 * speed_multiplier
             } else {
-                linear_velocity.x +=
-                    direction.x * (movement_acceleration.0 * 0.5) * time.delta_seconds();
-                linear_velocity.z -=
-                    direction.y * (movement_acceleration.0 * 0.5) * time.delta_seconds();
+                movement_acceleration.0
+                    * speed_multiplier
+                    * air_control.map_or(0.5, |control| control.0)
+            };
+
+            let horizontal = Vector::new(linear_velocity.x, 0.0, linear_velocity.z);
+            let mut new_horizontal = horizontal + direction * acceleration * delta_time.0;
+
+            // Input can accelerate the character up to the top speed, but never beyond it.
+            // Characters already moving faster (e.g. from a launch) aren't slowed down here.
+            if let Some(max_speed) = max_speed {
+                let top_speed = max_speed.0 * speed_multiplier;
+                new_horizontal =
+                    new_horizontal.clamp_length_max(top_speed.max(horizontal.length()));
             }
+
+            linear_velocity.x = new_horizontal.x;
+            linear_velocity.z = new_horizontal.z;
         }
 
-        if action_state.just_pressed(PlayerAction::Jump) {
-            if is_grounded {
-                linear_velocity.y = jump_impulse.0;
+        // Remember the jump press for the length of the buffer window,
+        // or only for this tick if the character has no buffer.
+        let jump_requested = match jump_buffer.as_deref_mut() {
+            Some(buffer) => {
+                buffer.0.tick(Duration::from_secs_f32(delta_time.0));
+                if intent.jump_pressed {
+                    buffer.0.reset();
+                }
+                buffer.is_active()
+            }
+            None => intent.jump_pressed,
+        };
+
+        let can_jump = is_grounded || coyote_time.as_ref().is_some_and(|c| c.is_active());
+
+        let can_air_jump = air_jumps.as_ref().is_some_and(|jumps| jumps.0 > 0);
+
+        if jump_requested && (can_jump || can_air_jump) {
+            linear_velocity.y = jump_impulse.0;
+            commands.entity(entity).insert(Jumping);
+
+            if !can_jump {
+                if let Some(air_jumps) = air_jumps.as_deref_mut() {
+                    air_jumps.0 -= 1;
+                }
+            }
+
+            // Consume both windows so a single press can't trigger more than one jump
+            if let Some(buffer) = jump_buffer.as_deref_mut() {
+                buffer.consume();
+            }
+            if let Some(coyote_time) = coyote_time.as_deref_mut() {
+                coyote_time.consume();
+            }
+        }
+
+        // Releasing jump while still rising cuts the jump short
+        if intent.jump_released && linear_velocity.y > 0.0 {
+            if let Some(jump_cut) = jump_cut {
+                linear_velocity.y *= jump_cut.0;
             }
         }
+
+        // The latched presses have been handled by this tick
+        intent.jump_pressed = false;
+        intent.jump_released = false;
+    }
+}
+
+/// Restores the air jumps of characters that have just landed.
+fn reset_air_jumps(mut query: Query<(&MaxAirJumps, &mut AirJumpsRemaining), Added<Grounded>>) {
+    for (max_air_jumps, mut air_jumps) in &mut query {
+        air_jumps.0 = max_air_jumps.0;
     }
 }
 
 /// Updates the [`Grounded`] status for character controllers.
+///
+/// The [`CoyoteTime`] window is restarted while the character is on the ground
+/// and starts running out once it leaves it. Characters that are [`Jumping`]
+/// aren't grounded, so a jump can't be followed by another ground jump.
+///
+/// Characters that were grounded and aren't moving upwards are snapped down
+/// to ground within their [`MaxStepHeight`], so walking down stairs or off
+/// small ledges doesn't make them briefly airborne.
+///
+/// The body the character stands on is stored in [`GroundedOn`]. When the character
+/// leaves it, the velocity of the body at the character's position is added to the
+/// character's own so it keeps the platform's momentum.
+#[allow(clippy::type_complexity)]
 fn update_grounded(
     mut commands: Commands,
+    delta_time: Res<DeltaTime>,
+    spatial_query: SolidSpatialQuery,
+    collider_parents: Query<&ColliderParent>,
+    platforms: Query<
+        (&Position, Option<&LinearVelocity>, Option<&AngularVelocity>),
+        Without<CharacterController>,
+    >,
     mut query: Query<
-        (Entity, &ShapeHits, &Rotation, Option<&MaxSlopeAngle>),
+        (
+            Entity,
+            &ShapeHits,
+            &Collider,
+            &mut Position,
+            &Rotation,
+            &mut LinearVelocity,
+            Option<&MaxSlopeAngle>,
+            Option<&MaxStepHeight>,
+            Option<&mut CoyoteTime>,
+            Option<&GroundedOn>,
+            Has<Grounded>,
+            Has<Jumping>,
+        ),
         With<CharacterController>,
     >,
 ) {
-    for (entity, hits, rotation, max_slope_angle) in &mut query {
-        // The character is grounded if the shape caster has a hit with a normal
-        // that isn't too steep.
-        let is_grounded = hits.iter().any(|hit| {
+    for (
+        entity,
+        hits,
+        collider,
+        mut position,
+        rotation,
+        mut linear_velocity,
+        max_slope_angle,
+        max_step_height,
+        coyote_time,
+        grounded_on,
+        was_grounded,
+        is_jumping,
+    ) in &mut query
+    {
+        let is_walkable = |normal: Vector| {
             if let Some(angle) = max_slope_angle {
-                rotation.rotate(-hit.normal2).angle_between(Vector::Y).abs() <= angle.0
+                normal.angle_between(Vector::Y).abs() <= angle.0
             } else {
                 true
             }
+        };
+
+        // The character is grounded if the shape caster has a hit with a normal
+        // that isn't too steep.
+        let mut ground_entity = hits
+            .iter()
+            .filter(|hit| spatial_query.is_solid(hit.entity))
+            .find(|hit| is_walkable(rotation.rotate(-hit.normal2)))
+            .map(|hit| hit.entity);
+
+        // Ignore the ground that was just jumped off until the character has left it
+        if is_jumping {
+            if ground_entity.is_some() && linear_velocity.y > 0.0 {
+                ground_entity = None;
+            } else {
+                commands.entity(entity).remove::<Jumping>();
+            }
+        }
+
+        if ground_entity.is_none() && was_grounded && linear_velocity.y <= 0.0 {
+            if let Some(max_step_height) = max_step_height {
+                let ground = spatial_query.cast_shape(
+                    &caster_shape(collider),
+                    position.0,
+                    rotation.0,
+                    Vector::NEG_Y,
+                    max_step_height.0,
+                    SpatialQueryFilter::default().without_entities([entity]),
+                );
+
+                if let Some(ground) = ground.filter(|ground| is_walkable(ground.normal1)) {
+                    position.y -= ground.time_of_impact;
+                    linear_velocity.y = 0.0;
+                    ground_entity = Some(ground.entity);
+                }
+            }
+        }
+
+        let is_grounded = ground_entity.is_some();
+
+        if let Some(mut coyote_time) = coyote_time {
+            if is_grounded {
+                coyote_time.0.reset();
+            } else {
+                coyote_time.0.tick(Duration::from_secs_f32(delta_time.0));
+            }
+        }
+
+        // Colliders can be children of the rigid body that actually moves
+        let ground_body = ground_entity.map(|ground| {
+            collider_parents
+                .get(ground)
+                .map_or(ground, |parent| parent.get())
         });
 
-        if is_grounded {
-            commands.entity(entity).insert(Grounded);
+        // Keep the platform's momentum when leaving it
+        if let Some(GroundedOn(platform)) = grounded_on {
+            if ground_body != Some(*platform) {
+                if let Ok(platform) = platforms.get(*platform) {
+                    linear_velocity.0 += platform_velocity_at(platform, position.0);
+                }
+            }
+        }
+
+        if let Some(ground_body) = ground_body {
+            commands
+                .entity(entity)
+                .insert((Grounded, GroundedOn(ground_body)));
         } else {
-            commands.entity(entity).remove::<Grounded>();
+            commands.entity(entity).remove::<(Grounded, GroundedOn)>();
+        }
+    }
+}
+
+/// Returns the velocity of a point attached to a body, taking its rotation into account.
+fn platform_velocity_at(
+    (platform_position, linear_velocity, angular_velocity): (
+        &Position,
+        Option<&LinearVelocity>,
+        Option<&AngularVelocity>,
+    ),
+    point: Vector,
+) -> Vector {
+    let linear = linear_velocity.map_or(Vector::ZERO, |velocity| velocity.0);
+    let angular = angular_velocity.map_or(Vector::ZERO, |velocity| velocity.0);
+    linear + angular.cross(point - platform_position.0)
+}
+
+/// Carries characters along with the body they are standing on.
+///
+/// The character is moved by the same displacement as the point of the body
+/// it stands on will be moved by this physics step, and turned by the body's yaw.
+fn move_with_platforms(
+    delta_time: Res<DeltaTime>,
+    platforms: Query<
+        (&Position, Option<&LinearVelocity>, Option<&AngularVelocity>),
+        Without<CharacterController>,
+    >,
+    mut characters: Query<(&GroundedOn, &mut Position, &mut Rotation), With<CharacterController>>,
+) {
+    let delta_seconds = delta_time.0;
+
+    for (grounded_on, mut position, mut rotation) in &mut characters {
+        let Ok((platform_position, linear_velocity, angular_velocity)) =
+            platforms.get(grounded_on.0)
+        else {
+            continue;
+        };
+
+        let linear = linear_velocity.map_or(Vector::ZERO, |velocity| velocity.0);
+        let angular = angular_velocity.map_or(Vector::ZERO, |velocity| velocity.0);
+
+        // Rotate the character's offset from the platform's center by the platform's rotation
+        let offset = position.0 - platform_position.0;
+        let step_rotation = Quaternion::from_scaled_axis(angular * delta_seconds);
+        let rotated_offset = step_rotation * offset;
+
+        position.0 += linear * delta_seconds + (rotated_offset - offset);
+        rotation.0 = Quaternion::from_rotation_y(angular.y * delta_seconds) * rotation.0;
+    }
+}
+
+/// Lifts grounded characters onto low obstacles in their way.
+///
+/// If a shape cast in the movement direction is blocked by something too steep
+/// to walk on, the same cast is repeated from [`MaxStepHeight`] higher up. When
+/// that path is clear and there is walkable ground on top of the obstacle,
+/// the character is moved up onto it.
+#[allow(clippy::type_complexity)]
+fn climb_steps(
+    delta_time: Res<DeltaTime>,
+    spatial_query: SolidSpatialQuery,
+    mut query: Query<
+        (
+            Entity,
+            &Collider,
+            &MaxStepHeight,
+            Option<&MaxSlopeAngle>,
+            &mut Position,
+            &Rotation,
+            &LinearVelocity,
+        ),
+        (With<CharacterController>, With<Grounded>),
+    >,
+) {
+    for (
+        entity,
+        collider,
+        max_step_height,
+        max_slope_angle,
+        mut position,
+        rotation,
+        linear_velocity,
+    ) in &mut query
+    {
+        let horizontal = Vector::new(linear_velocity.x, 0.0, linear_velocity.z);
+        let distance = horizontal.length() * delta_time.0;
+        if distance <= Scalar::EPSILON {
+            continue;
+        }
+        let direction = horizontal.normalize();
+
+        let is_walkable = |normal: Vector| {
+            normal.angle_between(Vector::Y).abs() <= max_slope_angle.map_or(PI * 0.25, |a| a.0)
+        };
+        let shape = caster_shape(collider);
+        let cast = |origin: Vector, direction: Vector, max_distance: Scalar| {
+            spatial_query.cast_shape(
+                &shape,
+                origin,
+                rotation.0,
+                direction,
+                max_distance,
+                SpatialQueryFilter::default().without_entities([entity]),
+            )
+        };
+
+        // Only obstacles that block horizontal movement can be stepped onto
+        let forward_distance = distance + STEP_SKIN;
+        let Some(obstacle) = cast(position.0, direction, forward_distance) else {
+            continue;
+        };
+        if is_walkable(obstacle.normal1) {
+            continue;
+        }
+
+        // There has to be room above the character and in front of it at step height
+        let step_height = max_step_height.0;
+        if cast(position.0, Vector::Y, step_height).is_some() {
+            continue;
+        }
+        let raised = position.0 + Vector::Y * step_height;
+        if cast(raised, direction, forward_distance).is_some() {
+            continue;
+        }
+
+        // Find the top of the step and move the character onto it
+        let Some(step) = cast(
+            raised + direction * forward_distance,
+            Vector::NEG_Y,
+            step_height,
+        ) else {
+            continue;
+        };
+        let climb = step_height - step.time_of_impact;
+        if climb > 0.0 && is_walkable(step.normal1) {
+            position.y += climb;
         }
     }
 }
 
 fn apply_gravity(
-    time: Res<Time>,
+    delta_time: Res<DeltaTime>,
     mut controllers: Query<(&ControllerGravity, &mut LinearVelocity)>,
 ) {
     for (gravity, mut linear_velocity) in &mut controllers {
-        linear_velocity.0 += gravity.0 * time.delta_seconds();
+        linear_velocity.0 += gravity.0 * delta_time.0;
     }
 }
 
-fn apply_movement_damping(mut query: Query<(&MovementDampingFactor, &mut LinearVelocity)>) {
+/// Accelerates characters down slopes that are too steep to stand on.
+///
+/// The slide direction is the downhill direction along the steepest
+/// ground hit, so the character follows the surface instead of falling through it.
+fn apply_slope_sliding(
+    delta_time: Res<DeltaTime>,
+    mut query: Query<
+        (
+            &ShapeHits,
+            &Rotation,
+            &MaxSlopeAngle,
+            &SlopeSlideAcceleration,
+            &mut LinearVelocity,
+        ),
+        (With<CharacterController>, Without<Grounded>),
+    >,
+) {
+    for (hits, rotation, max_slope_angle, slide_acceleration, mut linear_velocity) in &mut query {
+        // Walls (and overhangs) are not slopes, so only consider normals facing upwards
+        let steepest_normal = hits
+            .iter()
+            .map(|hit| rotation.rotate(-hit.normal2).normalize_or_zero())
+            .filter(|normal| {
+                normal.y > 0.01 && normal.angle_between(Vector::Y).abs() > max_slope_angle.0
+            })
+            .min_by(|a, b| a.y.total_cmp(&b.y));
+
+        let Some(normal) = steepest_normal else {
+            continue;
+        };
+
+        let downhill = (Vector::NEG_Y - normal * normal.dot(Vector::NEG_Y)).normalize_or_zero();
+
+        // Stop moving into the slope so all of the motion goes along it
+        let into_slope = linear_velocity.dot(normal);
+        if into_slope < 0.0 {
+            linear_velocity.0 -= normal * into_slope;
+        }
+
+        linear_velocity.0 += downhill * slide_acceleration.0 * delta_time.0;
+    }
+}
+
+fn apply_movement_damping(
+    delta_time: Res<DeltaTime>,
+    mut query: Query<(&MovementDampingFactor, &mut LinearVelocity)>,
+) {
     for (damping_factor, mut linear_velocity) in &mut query {
-        linear_velocity.x *= damping_factor.0;
-        linear_velocity.z *= damping_factor.0;
+        let decay = (-damping_factor.0 * delta_time.0).exp();
+        linear_velocity.x *= decay;
+        linear_velocity.z *= decay;
+    }
+}
+
+/// Kinematic bodies don't apply impulses to the bodies they collide with,
+/// so character controllers push dynamic bodies they are touching manually.
+///
+/// The push only happens along the ground plane and while the character
+/// is moving into the body, so standing next to a crate doesn't move it.
+#[allow(clippy::type_complexity)]
+fn push_dynamic_bodies(
+    delta_time: Res<DeltaTime>,
+    collisions: Res<Collisions>,
+    collider_parents: Query<&ColliderParent, Without<Sensor>>,
+    characters: Query<(&PushForce, &Rotation, &LinearVelocity), With<CharacterController>>,
+    mut bodies: Query<
+        (&RigidBody, &InverseMass, &mut LinearVelocity),
+        Without<CharacterController>,
+    >,
+) {
+    for contacts in collisions.iter() {
+        if !contacts.during_current_frame {
+            continue;
+        }
+
+        let Ok([collider_parent1, collider_parent2]) =
+            collider_parents.get_many([contacts.entity1, contacts.entity2])
+        else {
+            continue;
+        };
+
+        // Find out which of the bodies is the character
+        let (is_first, (push_force, rotation, character_velocity), body) =
+            if let Ok(character) = characters.get(collider_parent1.get()) {
+                (true, character, collider_parent2.get())
+            } else if let Ok(character) = characters.get(collider_parent2.get()) {
+                (false, character, collider_parent1.get())
+            } else {
+                continue;
+            };
+
+        let Ok((rb, inverse_mass, mut body_velocity)) = bodies.get_mut(body) else {
+            continue;
+        };
+
+        if !rb.is_dynamic() {
+            continue;
+        }
+
+        for manifold in contacts.manifolds.iter() {
+            // The contact normal pointing from the character towards the body
+            let normal = if is_first {
+                manifold.global_normal1(rotation)
+            } else {
+                manifold.global_normal2(rotation)
+            };
+
+            let push_direction = Vector::new(normal.x, 0.0, normal.z).normalize_or_zero();
+            if character_velocity.dot(push_direction) <= 0.0 {
+                continue;
+            }
+
+            let impulse = push_direction * push_force.0 * delta_time.0;
+            body_velocity.0 += impulse * inverse_mass.0;
+        }
     }
 }
 
@@ -287,7 +1527,8 @@ fn apply_movement_damping(mut query: Query<(&MovementDampingFactor, &mut LinearV
 ///
 /// This system performs very basic collision response for kinematic
 /// character controllers by pushing them along their contact normals
-/// by the current penetration depths.
+/// by the current penetration depths, and removing the part of their
+/// velocity that points into the surfaces they touch.
 #[allow(clippy::type_complexity)]
 fn kinematic_controller_collisions(
     collisions: Res<Collisions>,
@@ -350,12 +1591,22 @@ fn kinematic_controller_collisions(
                 position.0 += normal * contact.penetration;
             }
 
-            // If the slope isn't too steep to walk on but the character
-            // is falling, reset vertical velocity.
-            if max_slope_angle.is_some_and(|angle| normal.angle_between(Vector::Y).abs() <= angle.0)
-                && linear_velocity.y < 0.0
-            {
-                linear_velocity.y = linear_velocity.y.max(0.0);
+            let is_walkable = max_slope_angle
+                .is_some_and(|angle| normal.angle_between(Vector::Y).abs() <= angle.0);
+
+            if is_walkable {
+                // If the slope isn't too steep to walk on but the character
+                // is falling, reset vertical velocity.
+                if linear_velocity.y < 0.0 {
+                    linear_velocity.y = linear_velocity.y.max(0.0);
+                }
+            } else {
+                // For walls, steep slopes and ceilings, remove the velocity going into
+                // the surface so the character slides along it instead of sticking.
+                let into_surface = linear_velocity.dot(normal);
+                if into_surface < 0.0 {
+                    linear_velocity.0 -= normal * into_surface;
+                }
             }
         }
     }
@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_xpbd_3d::{math::*, prelude::*};
+use serde::Deserialize;
+
+use super::*;
+
+/// The tuning of a character controller, loaded from a `.character.ron` file.
+///
+/// Characters spawned from a config keep its [`Handle`], and pick up
+/// changes to the file while the game is running.
+#[derive(Asset, TypePath, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CharacterConfig {
+    /// The length of the standing capsule's cylinder part.
+    pub height: Scalar,
+    /// The length of the crouching capsule's cylinder part.
+    pub crouch_height: Scalar,
+    pub radius: Scalar,
+    /// The downward gravitational acceleration.
+    pub gravity: Scalar,
+    pub acceleration: Scalar,
+    pub damping: Scalar,
+    pub jump_impulse: Scalar,
+    /// The steepest walkable slope, in degrees.
+    pub max_slope_angle: Scalar,
+    pub max_speed: Scalar,
+    pub air_control: Scalar,
+    pub walk_speed: Scalar,
+    pub sprint_speed: Scalar,
+    pub crouch_speed: Scalar,
+    pub slope_slide_acceleration: Scalar,
+    pub max_step_height: Scalar,
+    pub push_force: Scalar,
+    pub turn_speed: Scalar,
+    /// In seconds.
+    pub coyote_time: f32,
+    /// In seconds.
+    pub jump_buffer: f32,
+    pub jump_cut: Scalar,
+    pub air_jumps: u32,
+}
+
+impl Default for CharacterConfig {
+    fn default() -> Self {
+        Self {
+            height: 1.25,
+            crouch_height: 0.6,
+            radius: 0.2,
+            gravity: 9.81 * 2.0,
+            acceleration: 30.0,
+            damping: 5.0,
+            jump_impulse: 7.0,
+            max_slope_angle: 30.0,
+            max_speed: 5.0,
+            air_control: 0.5,
+            walk_speed: 0.4,
+            sprint_speed: 1.6,
+            crouch_speed: 0.5,
+            slope_slide_acceleration: 20.0,
+            max_step_height: 0.3,
+            push_force: 80.0,
+            turn_speed: 12.0,
+            coyote_time: 0.12,
+            jump_buffer: 0.15,
+            jump_cut: 0.5,
+            air_jumps: 1,
+        }
+    }
+}
+
+impl CharacterConfig {
+    pub fn controller_bundle(&self) -> CharacterControllerBundle {
+        CharacterControllerBundle::new(
+            Collider::capsule(self.height, self.radius),
+            Vector::NEG_Y * self.gravity,
+        )
+        .with_movement(
+            self.acceleration,
+            self.damping,
+            self.jump_impulse,
+            self.max_slope_angle.to_radians(),
+        )
+        .with_speed_limits(self.max_speed, self.air_control)
+        .with_mode_speeds(self.walk_speed, self.sprint_speed, self.crouch_speed)
+        .with_crouch_collider(Collider::capsule(self.crouch_height, self.radius))
+        .with_slope_slide_acceleration(self.slope_slide_acceleration)
+        .with_max_step_height(self.max_step_height)
+        .with_push_force(self.push_force)
+        .with_turn_speed(self.turn_speed)
+        .with_jump_windows(self.coyote_time, self.jump_buffer)
+        .with_jump_cut(self.jump_cut)
+        .with_air_jumps(self.air_jumps)
+    }
+}
+
+/// Updates the tuning of characters whose config has been modified.
+///
+/// Only the parameters are replaced, so characters keep their velocity,
+/// movement mode and running jump timers. When the collider's height changes,
+/// the character is moved by half the difference so its feet stay in place.
+#[allow(clippy::type_complexity)]
+pub(super) fn apply_character_config(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<CharacterConfig>>,
+    configs: Res<Assets<CharacterConfig>>,
+    mut characters: Query<(
+        Entity,
+        &Handle<CharacterConfig>,
+        &MovementMode,
+        &mut Collider,
+        &mut ShapeCaster,
+        &mut Position,
+        &mut CoyoteTime,
+        &mut JumpBuffer,
+        &mut AirJumpsRemaining,
+    )>,
+) {
+    for event in asset_events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(config) = configs.get(*id) else {
+            continue;
+        };
+
+        for (
+            entity,
+            handle,
+            mode,
+            mut collider,
+            mut shape_caster,
+            mut position,
+            mut coyote_time,
+            mut jump_buffer,
+            mut air_jumps_remaining,
+        ) in &mut characters
+        {
+            if handle.id() != *id {
+                continue;
+            }
+
+            let CharacterControllerBundle {
+                crouch_shape,
+                gravity,
+                movement,
+                ..
+            } = config.controller_bundle();
+
+            let old_height = collider.shape().compute_local_aabb().extents().y;
+            *collider = if *mode == MovementMode::Crouch {
+                crouch_shape.crouching.clone()
+            } else {
+                crouch_shape.standing.clone()
+            };
+            shape_caster.shape = caster_shape(&collider);
+
+            let new_height = collider.shape().compute_local_aabb().extents().y;
+            position.0 += Vector::Y * (new_height - old_height) * 0.5;
+
+            coyote_time
+                .0
+                .set_duration(Duration::from_secs_f32(config.coyote_time));
+            jump_buffer
+                .0
+                .set_duration(Duration::from_secs_f32(config.jump_buffer));
+            air_jumps_remaining.0 = air_jumps_remaining.0.min(config.air_jumps);
+
+            commands.entity(entity).insert((
+                crouch_shape,
+                gravity,
+                (
+                    movement.acceleration,
+                    movement.damping,
+                    movement.mode_speeds,
+                    movement.max_speed,
+                    movement.air_control,
+                    movement.turn_speed,
+                    movement.jump_impulse,
+                ),
+                (
+                    movement.jump_cut,
+                    movement.max_air_jumps,
+                    movement.max_slope_angle,
+                    movement.slope_slide_acceleration,
+                    movement.max_step_height,
+                    movement.push_force,
+                ),
+            ));
+
+            info!("Applied the modified character config to {entity:?}");
+        }
+    }
+}
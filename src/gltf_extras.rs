@@ -0,0 +1,181 @@
+use bevy::{
+    gltf::GltfExtras,
+    pbr::CubemapVisibleEntities,
+    prelude::*,
+    render::{
+        primitives::{CubemapFrusta, Frustum},
+        view::VisibleEntities,
+    },
+    scene::SceneInstanceReady,
+};
+use bevy_xpbd_3d::prelude::*;
+use serde::Deserialize;
+
+use crate::level::{LevelTrigger, PlayerSpawnPoint};
+
+pub struct GltfExtrasPlugin;
+
+impl Plugin for GltfExtrasPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, apply_scene_extras);
+    }
+}
+
+/// A glTF scene whose nodes are set up from their extras (custom properties
+/// in Blender) once the scene has spawned.
+///
+/// The scene entity should be a [`RigidBody`], so the colliders created for
+/// its meshes become part of it.
+#[derive(Component)]
+pub struct ExtrasScene {
+    /// The collider created for meshes in nodes without a `collider` extra.
+    pub default_collider: Option<ComputedCollider>,
+}
+
+/// The extras of a glTF node that are understood by [`ExtrasScene`], e.g.
+/// `{"collider": "trimesh", "sensor": true, "level": "hallway"}`.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct NodeExtras {
+    /// The collider created for each mesh of the node.
+    collider: Option<ColliderExtra>,
+    /// Whether the node's colliders only detect overlaps instead of blocking.
+    sensor: bool,
+    spawn: Option<SpawnExtra>,
+    light: Option<LightExtra>,
+    /// The light's intensity, in lumens for point and spot lights.
+    intensity: Option<f32>,
+    /// The level loaded when a player enters the node's colliders.
+    level: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ColliderExtra {
+    TriMesh,
+    Convex,
+    Decomposition,
+    None,
+}
+
+impl ColliderExtra {
+    fn computed(self) -> Option<ComputedCollider> {
+        match self {
+            Self::TriMesh => Some(ComputedCollider::TriMesh),
+            Self::Convex => Some(ComputedCollider::ConvexHull),
+            Self::Decomposition => Some(ComputedCollider::ConvexDecomposition(
+                VHACDParameters::default(),
+            )),
+            Self::None => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum SpawnExtra {
+    Player,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum LightExtra {
+    Point,
+    Spot,
+}
+
+/// Attaches colliders, lights, spawn points and level triggers to the nodes
+/// of [`ExtrasScene`]s that have just finished spawning.
+fn apply_scene_extras(
+    mut commands: Commands,
+    mut ready_events: EventReader<SceneInstanceReady>,
+    scenes: Query<&ExtrasScene>,
+    children: Query<&Children>,
+    extras: Query<&GltfExtras>,
+    meshes: Query<(), With<Handle<Mesh>>>,
+) {
+    for event in ready_events.read() {
+        let Ok(scene) = scenes.get(event.parent) else {
+            continue;
+        };
+
+        // Meshes are children of their node and are set up along with it
+        let nodes = children
+            .iter_descendants(event.parent)
+            .filter(|entity| !meshes.contains(*entity));
+
+        for node in nodes {
+            let node_extras = match extras.get(node) {
+                Ok(extras) => serde_json::from_str(&extras.value).unwrap_or_else(|error| {
+                    warn!("Ignoring invalid extras on {node:?}: {error}");
+                    NodeExtras::default()
+                }),
+                Err(_) => NodeExtras::default(),
+            };
+
+            let collider = match node_extras.collider {
+                Some(collider) => collider.computed(),
+                None => scene.default_collider.clone(),
+            };
+
+            let node_meshes = children
+                .get(node)
+                .into_iter()
+                .flatten()
+                .filter(|child| meshes.contains(**child));
+
+            for mesh in node_meshes {
+                let Some(collider) = collider.clone() else {
+                    continue;
+                };
+
+                let mut mesh = commands.entity(*mesh);
+                mesh.insert(AsyncCollider(collider));
+                if node_extras.sensor {
+                    mesh.insert(Sensor);
+                }
+                if let Some(level) = &node_extras.level {
+                    mesh.insert(LevelTrigger {
+                        level: level.clone(),
+                    });
+                }
+            }
+
+            if let Some(SpawnExtra::Player) = node_extras.spawn {
+                commands.entity(node).insert(PlayerSpawnPoint);
+            }
+
+            match node_extras.light {
+                Some(LightExtra::Point) => {
+                    let mut light = PointLight {
+                        shadows_enabled: true,
+                        ..default()
+                    };
+                    if let Some(intensity) = node_extras.intensity {
+                        light.intensity = intensity;
+                    }
+                    commands.entity(node).insert((
+                        light,
+                        CubemapVisibleEntities::default(),
+                        CubemapFrusta::default(),
+                    ));
+                }
+                Some(LightExtra::Spot) => {
+                    let mut light = SpotLight {
+                        shadows_enabled: true,
+                        ..default()
+                    };
+                    if let Some(intensity) = node_extras.intensity {
+                        light.intensity = intensity;
+                    }
+                    commands.entity(node).insert((
+                        light,
+                        VisibleEntities::default(),
+                        Frustum::default(),
+                    ));
+                }
+                None => {}
+            }
+        }
+    }
+}
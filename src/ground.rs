@@ -1,25 +1,37 @@
 use bevy::prelude::*;
 use bevy_xpbd_3d::prelude::*;
 
-use crate::{AppState, GameAssets};
+use crate::{
+    gltf_extras::ExtrasScene,
+    level::{LevelAssets, LevelEntity},
+    AppState,
+};
 
 pub struct GroundPlugin;
 
 impl Plugin for GroundPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(AppState::Main), spawn_ground);
+        // Headless simulations have no level scene and build their own ground instead
+        app.add_systems(
+            OnEnter(AppState::Main),
+            spawn_ground.run_if(resource_exists::<LevelAssets>()),
+        );
     }
 }
 
-fn spawn_ground(mut commands: Commands, scene_assets: Res<GameAssets>) {
+fn spawn_ground(mut commands: Commands, level_assets: Res<LevelAssets>) {
     commands.spawn((
         SceneBundle {
-            scene: scene_assets.room.clone(),
+            scene: level_assets.scene.clone(),
             ..default()
         },
+        LevelEntity,
         RigidBody::Static,
-        AsyncSceneCollider::new(Some(ComputedCollider::ConvexDecomposition(
-            VHACDParameters::default(),
-        ))),
+        // Meshes without a `collider` extra are decomposed into convex parts
+        ExtrasScene {
+            default_collider: Some(ComputedCollider::ConvexDecomposition(
+                VHACDParameters::default(),
+            )),
+        },
     ));
 }
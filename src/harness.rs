@@ -0,0 +1,219 @@
+use bevy::{prelude::*, scene::ScenePlugin, utils::Instant};
+use bevy_xpbd_3d::{math::*, prelude::*};
+use leafwing_input_manager::{action_state::ActionState, axislike::DualAxisData};
+
+use crate::{
+    character::{CharacterConfig, CharacterControllerPlugin, Grounded, PlayerAction, PlayerId},
+    ground::GroundPlugin,
+    AppState,
+};
+
+/// The default physics tick rate of a simulation. Every [`SimulationHarness::step`]
+/// advances physics by exactly one tick, independent of the wall clock.
+pub const TICK_RATE: Scalar = 60.0;
+
+/// A headless app running the gameplay plugins under [`MinimalPlugins`],
+/// for driving character controllers with scripted input in tests.
+///
+/// Levels are built from primitive colliders instead of glTF scenes,
+/// and input is written straight into each character's [`ActionState`].
+pub struct SimulationHarness {
+    app: App,
+    players: usize,
+}
+
+impl Default for SimulationHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulationHarness {
+    pub fn new() -> Self {
+        Self::with_tick_rate(TICK_RATE)
+    }
+
+    /// Creates a simulation that runs physics at the given number of ticks per second.
+    pub fn with_tick_rate(tick_rate: Scalar) -> Self {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            // Needed by the scene collider systems of the physics plugins
+            AssetPlugin::default(),
+            ScenePlugin,
+        ))
+        .init_asset::<Mesh>()
+        .add_plugins(PhysicsPlugins::default())
+        .insert_resource(PhysicsTimestep::FixedOnce(1.0 / tick_rate))
+        .add_state::<AppState>()
+        .insert_resource(NextState(Some(AppState::Main)))
+        .add_plugins((GroundPlugin, CharacterControllerPlugin));
+
+        // Enter the main state before anything is spawned
+        app.update();
+
+        Self { app, players: 0 }
+    }
+
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    /// Spawns a static box with the given center and full extents.
+    pub fn spawn_box(&mut self, center: Vector, size: Vector) -> Entity {
+        self.spawn_rotated_box(center, size, Quaternion::IDENTITY)
+    }
+
+    /// Spawns a static box with the given center, full extents and rotation,
+    /// e.g. for ramps.
+    pub fn spawn_rotated_box(
+        &mut self,
+        center: Vector,
+        size: Vector,
+        rotation: Quaternion,
+    ) -> Entity {
+        self.app
+            .world
+            .spawn((
+                TransformBundle::from_transform(
+                    Transform::from_translation(center).with_rotation(rotation),
+                ),
+                Position(center),
+                Rotation(rotation),
+                RigidBody::Static,
+                Collider::cuboid(size.x, size.y, size.z),
+            ))
+            .id()
+    }
+
+    /// Spawns a kinematic box that moves at a constant velocity.
+    pub fn spawn_platform(&mut self, center: Vector, size: Vector, velocity: Vector) -> Entity {
+        self.app
+            .world
+            .spawn((
+                TransformBundle::from_transform(Transform::from_translation(center)),
+                Position(center),
+                RigidBody::Kinematic,
+                LinearVelocity(velocity),
+                Collider::cuboid(size.x, size.y, size.z),
+            ))
+            .id()
+    }
+
+    /// Spawns a large flat floor with its top at `y = 0`.
+    pub fn spawn_floor(&mut self) -> Entity {
+        self.spawn_box(Vector::NEG_Y * 0.5, Vector::new(100.0, 1.0, 100.0))
+    }
+
+    /// Spawns a player character with the default [`CharacterConfig`].
+    /// Characters get consecutive [`PlayerId`]s in spawn order.
+    pub fn spawn_character(&mut self, position: Vector) -> Entity {
+        self.spawn_character_with(position, &CharacterConfig::default())
+    }
+
+    /// Spawns a player character with the given tuning.
+    pub fn spawn_character_with(&mut self, position: Vector, config: &CharacterConfig) -> Entity {
+        let player = PlayerId(self.players);
+        self.players += 1;
+
+        self.app
+            .world
+            .spawn((
+                TransformBundle::from_transform(Transform::from_translation(position)),
+                Position(position),
+                player,
+                ActionState::<PlayerAction>::default(),
+                config.controller_bundle(),
+            ))
+            .id()
+    }
+
+    /// Spawns a camera for the player of a character, making the character's
+    /// movement input relative to it.
+    pub fn spawn_camera(&mut self, character: Entity, transform: Transform) -> Entity {
+        let player = *self
+            .app
+            .world
+            .get::<PlayerId>(character)
+            .expect("character should have a player id");
+
+        self.app
+            .world
+            .spawn((
+                Camera::default(),
+                TransformBundle::from_transform(transform),
+                player,
+            ))
+            .id()
+    }
+
+    /// Holds down an action until it is released.
+    pub fn press(&mut self, character: Entity, action: PlayerAction) {
+        self.action_state(character).press(action);
+    }
+
+    pub fn release(&mut self, character: Entity, action: PlayerAction) {
+        self.action_state(character).release(action);
+    }
+
+    /// Sets the movement stick of a character, where +Y is forward (world-space -Z).
+    /// A zero direction releases the stick.
+    pub fn set_movement(&mut self, character: Entity, direction: Vec2) {
+        let mut action_state = self.action_state(character);
+        if direction == Vec2::ZERO {
+            action_state.release(PlayerAction::Run);
+        } else {
+            action_state.press(PlayerAction::Run);
+            action_state.action_data_mut(PlayerAction::Run).axis_pair =
+                Some(DualAxisData::from_xy(direction));
+        }
+    }
+
+    /// Advances the simulation by the given number of physics ticks.
+    pub fn step(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.app.update();
+
+            // Turn this tick's presses and releases into held and idle states,
+            // like the input manager does at the start of every frame
+            let now = Instant::now();
+            let mut action_states = self.app.world.query::<&mut ActionState<PlayerAction>>();
+            for mut action_state in action_states.iter_mut(&mut self.app.world) {
+                action_state.tick(now, now);
+            }
+        }
+    }
+
+    pub fn position(&self, entity: Entity) -> Vector {
+        self.app
+            .world
+            .get::<Position>(entity)
+            .expect("entity should have a position")
+            .0
+    }
+
+    pub fn velocity(&self, entity: Entity) -> Vector {
+        self.app
+            .world
+            .get::<LinearVelocity>(entity)
+            .expect("entity should have a velocity")
+            .0
+    }
+
+    pub fn is_grounded(&self, character: Entity) -> bool {
+        self.app.world.get::<Grounded>(character).is_some()
+    }
+
+    fn action_state(&mut self, character: Entity) -> Mut<ActionState<PlayerAction>> {
+        self.app
+            .world
+            .get_mut::<ActionState<PlayerAction>>(character)
+            .expect("character should have an action state")
+    }
+}
@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::{math::*, prelude::*};
+use leafwing_input_manager::prelude::*;
+
+use crate::{character::PlayerAction, AppState};
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<InteractEvent>().add_systems(
+            Update,
+            (
+                find_interaction_targets,
+                apply_deferred,
+                send_interact_events,
+            )
+                .run_if(in_state(AppState::Main))
+                .chain(),
+        );
+    }
+}
+
+/// An entity that characters can use with [`PlayerAction::UseItem`].
+///
+/// The entity (or one of its children) needs a collider, usually a [`Sensor`],
+/// so it can be found with spatial queries.
+#[derive(Component)]
+pub struct Interactable {
+    /// The text shown to the player when the interactable is in reach.
+    pub prompt: String,
+}
+
+/// How far away from a character interactables can be used.
+#[derive(Component)]
+pub struct InteractionRange(pub Scalar);
+
+/// The nearest interactable in reach of a character.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct InteractionTarget(pub Entity);
+
+/// Sent when a character uses the interactable it is targeting.
+#[derive(Event)]
+pub struct InteractEvent {
+    pub actor: Entity,
+    pub target: Entity,
+}
+
+/// Finds the nearest interactable within range of each character
+/// that isn't hidden behind other geometry.
+fn find_interaction_targets(
+    mut commands: Commands,
+    spatial_query: SpatialQuery,
+    characters: Query<(Entity, &Position, &InteractionRange)>,
+    collider_parents: Query<&ColliderParent>,
+    interactables: Query<&GlobalTransform, With<Interactable>>,
+) {
+    // Colliders can be children of the interactable entity
+    let interactable_of = |collider: Entity| {
+        if interactables.contains(collider) {
+            Some(collider)
+        } else {
+            collider_parents
+                .get(collider)
+                .ok()
+                .map(|parent| parent.get())
+                .filter(|parent| interactables.contains(*parent))
+        }
+    };
+
+    for (entity, position, range) in &characters {
+        let nearest = spatial_query
+            .shape_intersections(
+                &Collider::ball(range.0),
+                position.0,
+                Quaternion::IDENTITY,
+                SpatialQueryFilter::default().without_entities([entity]),
+            )
+            .into_iter()
+            .filter_map(interactable_of)
+            .filter_map(|target| {
+                let target_position = interactables.get(target).ok()?.translation();
+                let to_target = target_position - position.0;
+
+                // The interactable has to be the first thing hit on the way to it
+                let is_visible = spatial_query
+                    .cast_ray(
+                        position.0,
+                        to_target.normalize_or_zero(),
+                        to_target.length(),
+                        true,
+                        SpatialQueryFilter::default().without_entities([entity]),
+                    )
+                    .map_or(true, |hit| interactable_of(hit.entity) == Some(target));
+
+                is_visible.then_some((target, to_target.length()))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(target, _)| target);
+
+        match nearest {
+            Some(target) => commands.entity(entity).insert(InteractionTarget(target)),
+            None => commands.entity(entity).remove::<InteractionTarget>(),
+        };
+    }
+}
+
+fn send_interact_events(
+    characters: Query<(Entity, &ActionState<PlayerAction>, &InteractionTarget)>,
+    mut interact_events: EventWriter<InteractEvent>,
+) {
+    for (entity, action_state, target) in &characters {
+        if action_state.just_pressed(PlayerAction::UseItem) {
+            interact_events.send(InteractEvent {
+                actor: entity,
+                target: target.0,
+            });
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use leafwing_input_manager::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    character::PlayerAction,
+    interaction::{InteractEvent, Interactable},
+    level::{CurrentLevel, LevelEntity, LevelManifest},
+    ron_asset::RonAssetPlugin,
+    AppState, GameAssets,
+};
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<ItemDefinition>::new(&["item.ron"]))
+            .add_systems(OnEnter(AppState::Main), spawn_pickups)
+            .add_systems(
+                Update,
+                (pick_up_items, select_item, update_held_item).run_if(in_state(AppState::Main)),
+            );
+    }
+}
+
+/// The definition of an item type, loaded from an `.item.ron` file.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct ItemDefinition {
+    pub name: String,
+    /// The asset path of the scene shown for the item in the world and in the character's hand.
+    pub scene: String,
+    /// How many of the item fit into a single inventory slot.
+    pub max_stack: u32,
+}
+
+/// A number of items of the same type in an inventory slot.
+#[derive(Clone)]
+pub struct ItemStack {
+    pub item: Handle<ItemDefinition>,
+    pub count: u32,
+}
+
+/// The items a character is carrying, and the slot it currently has selected.
+#[derive(Component)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+    selected: usize,
+}
+
+impl Inventory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: vec![None; capacity],
+            selected: 0,
+        }
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_item(&self) -> Option<&ItemStack> {
+        self.slots[self.selected].as_ref()
+    }
+
+    /// Moves the selection by `offset` slots, wrapping around at either end.
+    pub fn cycle_selection(&mut self, offset: isize) {
+        let capacity = self.slots.len() as isize;
+        self.selected = (self.selected as isize + offset).rem_euclid(capacity) as usize;
+    }
+
+    /// Adds items to the inventory, filling up existing stacks of the same item first.
+    /// Returns the number of items that didn't fit.
+    pub fn add(&mut self, item: &Handle<ItemDefinition>, max_stack: u32, mut count: u32) -> u32 {
+        for stack in self.slots.iter_mut().flatten() {
+            if stack.item == *item && stack.count < max_stack {
+                let added = count.min(max_stack - stack.count);
+                stack.count += added;
+                count -= added;
+            }
+        }
+
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if count == 0 {
+                break;
+            }
+            let added = count.min(max_stack);
+            *slot = Some(ItemStack {
+                item: item.clone(),
+                count: added,
+            });
+            count -= added;
+        }
+
+        count
+    }
+}
+
+/// An item lying in the world that can be picked up by interacting with it.
+#[derive(Component)]
+pub struct ItemPickup {
+    pub item: Handle<ItemDefinition>,
+    pub count: u32,
+}
+
+/// The name of the bone in a character's model that held items are attached to.
+#[derive(Component)]
+pub struct HandBone(pub String);
+
+/// The scene of the item a character is holding, attached to its [`HandBone`].
+#[derive(Component)]
+struct HeldItem {
+    owner: Entity,
+    item: AssetId<ItemDefinition>,
+}
+
+/// Places the pickups listed for the current level in the [`LevelManifest`].
+fn spawn_pickups(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    manifests: Res<Assets<LevelManifest>>,
+    current_level: Res<CurrentLevel>,
+    items: Res<Assets<ItemDefinition>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some(level) = manifests
+        .get(&game_assets.levels)
+        .and_then(|manifest| manifest.level(&current_level.0))
+    else {
+        return;
+    };
+
+    for pickup in &level.pickups {
+        let Some((handle, item)) = game_assets.items.iter().find_map(|handle| {
+            items
+                .get(handle)
+                .filter(|item| item.name == pickup.item)
+                .map(|item| (handle, item))
+        }) else {
+            warn!(
+                "Level {} has a pickup of unknown item {}",
+                level.name, pickup.item
+            );
+            continue;
+        };
+
+        commands.spawn((
+            SceneBundle {
+                scene: asset_server.load(item.scene.clone()),
+                transform: Transform::from_translation(pickup.position),
+                ..default()
+            },
+            ItemPickup {
+                item: handle.clone(),
+                count: pickup.count,
+            },
+            Interactable {
+                prompt: format!("Pick up {}", item.name),
+            },
+            LevelEntity,
+            RigidBody::Static,
+            Collider::ball(0.3),
+            Sensor,
+        ));
+    }
+}
+
+/// Moves picked up items into the inventory of the character that used them.
+/// Pickups that don't fit completely stay in the world with the remaining count.
+fn pick_up_items(
+    mut commands: Commands,
+    mut interact_events: EventReader<InteractEvent>,
+    items: Res<Assets<ItemDefinition>>,
+    mut pickups: Query<&mut ItemPickup>,
+    mut inventories: Query<&mut Inventory>,
+) {
+    for event in interact_events.read() {
+        let (Ok(mut pickup), Ok(mut inventory)) = (
+            pickups.get_mut(event.target),
+            inventories.get_mut(event.actor),
+        ) else {
+            continue;
+        };
+        let Some(item) = items.get(&pickup.item) else {
+            continue;
+        };
+
+        let remaining = inventory.add(&pickup.item, item.max_stack, pickup.count);
+
+        if remaining == 0 {
+            commands.entity(event.target).despawn_recursive();
+        } else {
+            pickup.count = remaining;
+        }
+    }
+}
+
+fn select_item(mut query: Query<(&ActionState<PlayerAction>, &mut Inventory)>) {
+    for (action_state, mut inventory) in &mut query {
+        if action_state.just_pressed(PlayerAction::NextItem) {
+            inventory.cycle_selection(1);
+        }
+        if action_state.just_pressed(PlayerAction::PreviousItem) {
+            inventory.cycle_selection(-1);
+        }
+    }
+}
+
+/// Attaches the scene of the selected item to the character's hand bone,
+/// replacing the previously held item.
+///
+/// The bone only exists once the character's scene has been spawned,
+/// so this keeps retrying until it is found.
+fn update_held_item(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    items: Res<Assets<ItemDefinition>>,
+    characters: Query<(Entity, &Inventory, &HandBone)>,
+    children: Query<&Children>,
+    names: Query<&Name>,
+    held_items: Query<(Entity, &HeldItem)>,
+) {
+    for (character, inventory, hand_bone) in &characters {
+        let selected = inventory.selected_item().map(|stack| &stack.item);
+        let held = held_items
+            .iter()
+            .find(|(_, held_item)| held_item.owner == character);
+
+        if held.map(|(_, held_item)| held_item.item) == selected.map(|item| item.id()) {
+            continue;
+        }
+
+        if let Some((entity, _)) = held {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        let Some((handle, item)) = selected.and_then(|handle| Some((handle, items.get(handle)?)))
+        else {
+            continue;
+        };
+
+        let Some(bone) = children.iter_descendants(character).find(|entity| {
+            names
+                .get(*entity)
+                .is_ok_and(|name| name.as_str() == hand_bone.0)
+        }) else {
+            continue;
+        };
+
+        let held_item = commands
+            .spawn((
+                SceneBundle {
+                    scene: asset_server.load(item.scene.clone()),
+                    ..default()
+                },
+                HeldItem {
+                    owner: character,
+                    item: handle.id(),
+                },
+            ))
+            .id();
+        commands.entity(bone).add_child(held_item);
+    }
+}
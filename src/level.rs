@@ -0,0 +1,282 @@
+use bevy::prelude::*;
+use bevy_asset_loader::prelude::*;
+use bevy_xpbd_3d::{math::*, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    character::{CharacterController, PlayerId},
+    navigation::NavMesh,
+    ron_asset::RonAssetPlugin,
+    AppState, GameAssets,
+};
+
+/// The dynamic asset key of the current level's scene.
+const LEVEL_SCENE_KEY: &str = "level.scene";
+
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<LevelManifest>::new(&["levels.ron"]))
+            .add_event::<ChangeLevel>()
+            .init_resource::<CurrentLevel>()
+            .add_loading_state(
+                LoadingState::new(AppState::LoadingLevel)
+                    .continue_to_state(AppState::Main)
+                    .load_collection::<LevelAssets>(),
+            )
+            .add_systems(OnExit(AppState::Loading), load_start_level)
+            .add_systems(OnEnter(AppState::LoadingLevel), despawn_level)
+            .add_systems(OnEnter(AppState::Main), (spawn_triggers, place_players))
+            .add_systems(
+                Update,
+                (
+                    place_players_at_spawn_points,
+                    (enter_triggers, change_level).chain(),
+                )
+                    .run_if(in_state(AppState::Main)),
+            );
+    }
+}
+
+/// The list of levels in the game, loaded from a `.levels.ron` file.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct LevelManifest {
+    /// The name of the level the game starts in.
+    pub start: String,
+    pub levels: Vec<LevelDefinition>,
+}
+
+impl LevelManifest {
+    pub fn level(&self, name: &str) -> Option<&LevelDefinition> {
+        self.levels.iter().find(|level| level.name == name)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LevelDefinition {
+    pub name: String,
+    /// The asset path of the level's glTF scene.
+    pub scene: String,
+    /// Where the first player is placed when entering the level.
+    /// Other players are lined up next to them.
+    pub spawn_point: Vec3,
+    #[serde(default)]
+    pub triggers: Vec<TriggerDefinition>,
+    #[serde(default)]
+    pub pickups: Vec<PickupDefinition>,
+}
+
+/// A box that loads another level when a player walks into it.
+#[derive(Deserialize)]
+pub struct TriggerDefinition {
+    pub center: Vec3,
+    /// The full extents of the box.
+    pub size: Vec3,
+    /// The name of the level to load.
+    pub level: String,
+}
+
+/// An item lying in the level that players can pick up.
+#[derive(Deserialize)]
+pub struct PickupDefinition {
+    /// The name of the item, as given in its [`ItemDefinition`](crate::inventory::ItemDefinition).
+    pub item: String,
+    pub position: Vec3,
+    #[serde(default = "PickupDefinition::default_count")]
+    pub count: u32,
+}
+
+impl PickupDefinition {
+    fn default_count() -> u32 {
+        1
+    }
+}
+
+/// The assets of the current level, resolved from the dynamic assets
+/// registered for it in the [`LevelManifest`].
+#[derive(AssetCollection, Resource)]
+pub struct LevelAssets {
+    #[asset(key = "level.scene")]
+    pub scene: Handle<Scene>,
+}
+
+/// The name of the level that is loaded or being loaded.
+#[derive(Resource, Default)]
+pub struct CurrentLevel(pub String);
+
+/// A marker for entities that belong to the current level
+/// and are despawned when another level is loaded.
+#[derive(Component)]
+pub struct LevelEntity;
+
+/// Loads the level with the given name.
+#[derive(Event)]
+pub struct ChangeLevel(pub String);
+
+/// Where the first player is placed when the level is entered.
+/// Other players are lined up next to them.
+#[derive(Component)]
+pub struct PlayerSpawnPoint;
+
+/// Switches levels when a player enters it.
+#[derive(Component)]
+pub struct LevelTrigger {
+    pub level: String,
+}
+
+/// Registers the dynamic assets of a level, so they are loaded
+/// the next time [`AppState::LoadingLevel`] is entered.
+fn queue_level(
+    level: &LevelDefinition,
+    dynamic_assets: &mut DynamicAssets,
+    current_level: &mut CurrentLevel,
+) {
+    // The keys have to be registered before the loading state is entered
+    dynamic_assets.register_asset(
+        LEVEL_SCENE_KEY,
+        Box::new(StandardDynamicAsset::File {
+            path: level.scene.clone(),
+        }),
+    );
+
+    info!("Loading level {}", level.name);
+    current_level.0 = level.name.clone();
+}
+
+/// Queues the start level, which is loaded right after the game assets.
+fn load_start_level(
+    game_assets: Res<GameAssets>,
+    manifests: Res<Assets<LevelManifest>>,
+    mut dynamic_assets: ResMut<DynamicAssets>,
+    mut current_level: ResMut<CurrentLevel>,
+) {
+    let manifest = manifests
+        .get(&game_assets.levels)
+        .expect("the level manifest should be loaded with the game assets");
+    let level = manifest
+        .level(&manifest.start)
+        .unwrap_or_else(|| panic!("the start level {} is not in the manifest", manifest.start));
+
+    queue_level(level, &mut dynamic_assets, &mut current_level);
+}
+
+/// Despawns the previous level, and drops its [`NavMesh`] until the next one is baked.
+fn despawn_level(
+    mut commands: Commands,
+    mut nav_mesh: ResMut<NavMesh>,
+    level_entities: Query<Entity, With<LevelEntity>>,
+) {
+    for entity in &level_entities {
+        commands.entity(entity).despawn_recursive();
+    }
+    *nav_mesh = NavMesh::default();
+}
+
+fn spawn_triggers(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    manifests: Res<Assets<LevelManifest>>,
+    current_level: Res<CurrentLevel>,
+) {
+    let Some(level) = manifests
+        .get(&game_assets.levels)
+        .and_then(|manifest| manifest.level(&current_level.0))
+    else {
+        return;
+    };
+
+    for trigger in &level.triggers {
+        commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(trigger.center)),
+            LevelTrigger {
+                level: trigger.level.clone(),
+            },
+            LevelEntity,
+            RigidBody::Static,
+            Collider::cuboid(trigger.size.x, trigger.size.y, trigger.size.z),
+            Sensor,
+        ));
+    }
+}
+
+/// Moves the players to the spawn point of the level that was just entered.
+fn place_players(
+    game_assets: Res<GameAssets>,
+    manifests: Res<Assets<LevelManifest>>,
+    current_level: Res<CurrentLevel>,
+    mut players: Query<(&PlayerId, &mut Position, &mut LinearVelocity), With<CharacterController>>,
+) {
+    let Some(level) = manifests
+        .get(&game_assets.levels)
+        .and_then(|manifest| manifest.level(&current_level.0))
+    else {
+        return;
+    };
+
+    move_players_to(level.spawn_point, &mut players);
+}
+
+/// Moves the players to spawn points placed in the level's scene, which
+/// take precedence over the spawn point in the [`LevelManifest`].
+fn place_players_at_spawn_points(
+    spawn_points: Query<&GlobalTransform, Added<PlayerSpawnPoint>>,
+    mut players: Query<(&PlayerId, &mut Position, &mut LinearVelocity), With<CharacterController>>,
+) {
+    if let Some(spawn_point) = spawn_points.iter().next() {
+        move_players_to(spawn_point.translation(), &mut players);
+    }
+}
+
+fn move_players_to(
+    spawn_point: Vector,
+    players: &mut Query<(&PlayerId, &mut Position, &mut LinearVelocity), With<CharacterController>>,
+) {
+    for (player, mut position, mut velocity) in players {
+        position.0 = spawn_point + Vector::X * 2.0 * player.0 as Scalar;
+        velocity.0 = Vector::ZERO;
+    }
+}
+
+fn enter_triggers(
+    mut collision_events: EventReader<CollisionStarted>,
+    triggers: Query<&LevelTrigger>,
+    players: Query<(), (With<PlayerId>, With<CharacterController>)>,
+    mut change_level: EventWriter<ChangeLevel>,
+) {
+    for CollisionStarted(a, b) in collision_events.read() {
+        let (trigger, other) = match (triggers.get(*a), triggers.get(*b)) {
+            (Ok(trigger), _) => (trigger, *b),
+            (_, Ok(trigger)) => (trigger, *a),
+            _ => continue,
+        };
+
+        if players.contains(other) {
+            change_level.send(ChangeLevel(trigger.level.clone()));
+        }
+    }
+}
+
+fn change_level(
+    mut events: EventReader<ChangeLevel>,
+    game_assets: Res<GameAssets>,
+    manifests: Res<Assets<LevelManifest>>,
+    mut dynamic_assets: ResMut<DynamicAssets>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    // Only the last request matters if several triggers fire at once
+    let Some(ChangeLevel(name)) = events.read().last() else {
+        return;
+    };
+    let Some(level) = manifests
+        .get(&game_assets.levels)
+        .and_then(|manifest| manifest.level(name))
+    else {
+        warn!("Can't load level {name}, it's not in the manifest");
+        return;
+    };
+
+    queue_level(level, &mut dynamic_assets, &mut current_level);
+    next_state.set(AppState::LoadingLevel);
+}
@@ -0,0 +1,50 @@
+pub mod ai;
+pub mod animation;
+pub mod bindings;
+pub mod camera;
+pub mod character;
+pub mod debug;
+pub mod gltf_extras;
+pub mod ground;
+pub mod harness;
+pub mod interaction;
+pub mod inventory;
+pub mod level;
+pub mod light;
+pub mod navigation;
+pub mod replay;
+pub mod ron_asset;
+pub mod ui;
+
+use bevy::{gltf::Gltf, prelude::*};
+use bevy_asset_loader::prelude::*;
+
+#[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
+pub enum AppState {
+    #[default]
+    Loading,
+    LoadingLevel,
+    Main,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct GameAssets {
+    #[asset(path = "models/character.glb#Scene0")]
+    pub character: Handle<Scene>,
+
+    #[asset(path = "characters/player.character.ron")]
+    pub character_config: Handle<character::CharacterConfig>,
+
+    #[asset(path = "characters/npc.character.ron")]
+    pub npc_config: Handle<character::CharacterConfig>,
+
+    /// The character's glTF file, whose animations are looked up by name.
+    #[asset(path = "models/character.glb")]
+    pub character_model: Handle<Gltf>,
+
+    #[asset(path = "levels/manifest.levels.ron")]
+    pub levels: Handle<level::LevelManifest>,
+
+    #[asset(path = "items", collection(typed))]
+    pub items: Vec<Handle<inventory::ItemDefinition>>,
+}
@@ -1,53 +1,48 @@
-mod camera;
-mod character;
-mod debug;
-mod ground;
-mod light;
-mod ui;
-
 use bevy::{diagnostic::FrameTimeDiagnosticsPlugin, prelude::*};
 use bevy_asset_loader::prelude::*;
 use bevy_editor_pls::prelude::*;
 use bevy_third_person_camera::ThirdPersonCameraPlugin;
 use bevy_xpbd_3d::prelude::*;
+use holder::{
+    ai, animation, bindings, camera, character, gltf_extras, ground, interaction, inventory, level,
+    light, navigation, replay, ui, AppState, GameAssets,
+};
 use leafwing_input_manager::prelude::*;
 
-#[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
-pub enum AppState {
-    #[default]
-    Loading,
-    Main,
-}
-
-#[derive(AssetCollection, Resource)]
-pub struct GameAssets {
-    #[asset(path = "models/character.glb#Scene0")]
-    pub character: Handle<Scene>,
-
-    #[asset(path = "terrains/room.glb#Scene0")]
-    pub room: Handle<Scene>,
-}
-
 fn main() {
     App::new()
         .insert_resource(ClearColor(Color::rgb(0.1, 0.0, 0.15)))
         .add_plugins(DefaultPlugins)
         .add_state::<AppState>()
+        .insert_resource(character::LocalPlayers::from_args())
         .add_loading_state(
             LoadingState::new(AppState::Loading)
-                .continue_to_state(AppState::Main)
+                .continue_to_state(AppState::LoadingLevel)
                 .load_collection::<GameAssets>(),
         )
         .add_plugins(FrameTimeDiagnosticsPlugin::default())
         .add_plugins(ui::fps_counter::FpsCounterPlugin)
+        .add_plugins(ui::rebind_menu::RebindMenuPlugin)
+        .add_plugins(ui::interaction_prompt::InteractionPromptPlugin)
+        .add_plugins(ui::hotbar::HotbarPlugin)
         .add_plugins(EditorPlugin::default())
         .add_plugins(PhysicsPlugins::default())
         .add_plugins(PhysicsDebugPlugin::default())
         .add_plugins(InputManagerPlugin::<character::PlayerAction>::default())
         //User defined plugins
+        .add_plugins(bindings::BindingsPlugin)
+        .add_plugins(level::LevelPlugin)
         .add_plugins(light::LightPlugin)
         .add_plugins(ground::GroundPlugin)
+        .add_plugins(gltf_extras::GltfExtrasPlugin)
         .add_plugins(character::CharacterControllerPlugin)
+        .add_plugins(character::PlayerPlugin)
+        .add_plugins(animation::CharacterAnimationPlugin)
+        .add_plugins(interaction::InteractionPlugin)
+        .add_plugins(inventory::InventoryPlugin)
+        .add_plugins(navigation::NavigationPlugin)
+        .add_plugins(ai::AiPlugin)
+        .add_plugins(replay::ReplayPlugin)
         .add_plugins(ThirdPersonCameraPlugin)
         .add_plugins(camera::CameraPlugin)
         .run()
@@ -0,0 +1,479 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use bevy_xpbd_3d::{math::*, prelude::*};
+
+use crate::{character::CharacterConfig, AppState, GameAssets};
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavMeshSettings>()
+            .init_resource::<NavMesh>()
+            .init_resource::<NavMeshDebug>()
+            .add_systems(OnExit(AppState::Loading), match_player_character)
+            .add_systems(
+                Update,
+                (
+                    match_player_character.run_if(on_event::<AssetEvent<CharacterConfig>>()),
+                    bake_nav_mesh,
+                    toggle_nav_mesh_debug,
+                    draw_nav_mesh,
+                )
+                    .run_if(in_state(AppState::Main)),
+            );
+    }
+}
+
+/// The parameters used for baking the [`NavMesh`].
+///
+/// The agent size and slope limits are taken from the player's [`CharacterConfig`],
+/// so every cell in the mesh is somewhere a character can actually stand.
+#[derive(Resource)]
+pub struct NavMeshSettings {
+    /// The size of a single navigation cell along the X and Z axes.
+    pub cell_size: Scalar,
+    pub agent_radius: Scalar,
+    pub agent_height: Scalar,
+    pub max_slope_angle: Scalar,
+    /// The largest height difference between neighbouring cells that is still connected.
+    pub max_step_height: Scalar,
+}
+
+impl NavMeshSettings {
+    /// Sets the agent size and slope limits to those of a character.
+    pub fn match_character(&mut self, config: &CharacterConfig) {
+        self.agent_radius = config.radius;
+        self.agent_height = config.height + config.radius * 2.0;
+        self.max_slope_angle = config.max_slope_angle.to_radians();
+        self.max_step_height = config.max_step_height;
+    }
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        let mut settings = Self {
+            cell_size: 0.25,
+            agent_radius: 0.0,
+            agent_height: 0.0,
+            max_slope_angle: 0.0,
+            max_step_height: 0.0,
+        };
+        settings.match_character(&CharacterConfig::default());
+        settings
+    }
+}
+
+/// Toggles drawing the [`NavMesh`] with gizmos. Press F10 to toggle it.
+#[derive(Resource, Default)]
+pub struct NavMeshDebug(pub bool);
+
+/// A walkable-area representation of the static level geometry.
+///
+/// The level is divided into a grid of cells on the ground plane, and each
+/// cell stores the height of the walkable ground in it, if there is any.
+/// Only the topmost walkable surface of each cell is kept.
+#[derive(Resource, Default)]
+pub struct NavMesh {
+    origin: Vec2,
+    cell_size: Scalar,
+    width: usize,
+    depth: usize,
+    max_step_height: Scalar,
+    heights: Vec<Option<Scalar>>,
+}
+
+type Cell = (usize, usize);
+
+impl NavMesh {
+    pub fn is_baked(&self) -> bool {
+        !self.heights.is_empty()
+    }
+
+    /// Bakes a mesh covering the combined bounds of the static level geometry.
+    ///
+    /// Returns an unbaked mesh if there is no static geometry.
+    pub fn bake(geometry: &NavMeshGeometry, settings: &NavMeshSettings) -> Self {
+        // Only static geometry is part of the navigation mesh
+        let (solid, ignored): (Vec<_>, Vec<_>) =
+            geometry
+                .colliders
+                .iter()
+                .partition(|(_, parent, _, is_sensor)| {
+                    !is_sensor
+                        && geometry
+                            .rigid_bodies
+                            .get(parent.get())
+                            .is_ok_and(|rb| rb.is_static())
+                });
+
+        let Some((min, max)) = solid
+            .iter()
+            .map(|(_, _, aabb, _)| {
+                (
+                    Vector::new(aabb.mins.x, aabb.mins.y, aabb.mins.z),
+                    Vector::new(aabb.maxs.x, aabb.maxs.y, aabb.maxs.z),
+                )
+            })
+            .reduce(|(min, max), (aabb_min, aabb_max)| (min.min(aabb_min), max.max(aabb_max)))
+        else {
+            return Self::default();
+        };
+
+        let ignored: Vec<Entity> = ignored.into_iter().map(|(entity, ..)| entity).collect();
+        let filter = || SpatialQueryFilter::default().without_entities(ignored.iter().copied());
+
+        let clearance_shape = Collider::capsule(
+            (settings.agent_height - settings.agent_radius * 2.0).max(0.0),
+            settings.agent_radius,
+        );
+
+        let origin = Vec2::new(min.x, min.z);
+        let width = ((max.x - min.x) / settings.cell_size).ceil().max(1.0) as usize;
+        let depth = ((max.z - min.z) / settings.cell_size).ceil().max(1.0) as usize;
+
+        // The ground rays start just above the highest geometry
+        let ceiling = max.y + 0.1;
+        let ray_length = max.y - min.y + 0.2;
+
+        let mut heights = Vec::with_capacity(width * depth);
+
+        for z in 0..depth {
+            for x in 0..width {
+                let center = origin + (Vec2::new(x as f32, z as f32) + 0.5) * settings.cell_size;
+                let ray_origin = Vector::new(center.x, ceiling, center.y);
+
+                let height = geometry
+                    .spatial_query
+                    .cast_ray(ray_origin, Vector::NEG_Y, ray_length, true, filter())
+                    .filter(|hit| {
+                        hit.normal.angle_between(Vector::Y).abs() <= settings.max_slope_angle
+                    })
+                    .map(|hit| ceiling - hit.time_of_impact)
+                    .filter(|&height| {
+                        // The agent has to fit on top of the ground without touching anything
+                        let agent_center = Vector::new(
+                            center.x,
+                            height + settings.agent_height * 0.5 + settings.max_step_height,
+                            center.y,
+                        );
+                        geometry
+                            .spatial_query
+                            .shape_intersections(
+                                &clearance_shape,
+                                agent_center,
+                                Quaternion::IDENTITY,
+                                filter(),
+                            )
+                            .is_empty()
+                    });
+
+                heights.push(height);
+            }
+        }
+
+        Self {
+            origin,
+            cell_size: settings.cell_size,
+            width,
+            depth,
+            max_step_height: settings.max_step_height,
+            heights,
+        }
+    }
+
+    /// Returns the grid cell containing the given point, ignoring its height.
+    fn cell_at(&self, point: Vector) -> Option<Cell> {
+        let local = (Vec2::new(point.x, point.z) - self.origin) / self.cell_size;
+        if local.x < 0.0 || local.y < 0.0 {
+            return None;
+        }
+
+        let cell = (local.x as usize, local.y as usize);
+        (cell.0 < self.width && cell.1 < self.depth).then_some(cell)
+    }
+
+    fn height(&self, (x, z): Cell) -> Option<Scalar> {
+        self.heights[z * self.width + x]
+    }
+
+    /// Returns the point on the ground in the middle of a walkable cell.
+    fn cell_position(&self, cell: Cell) -> Option<Vector> {
+        let height = self.height(cell)?;
+        let center = self.origin + (Vec2::new(cell.0 as f32, cell.1 as f32) + 0.5) * self.cell_size;
+        Some(Vector::new(center.x, height, center.y))
+    }
+
+    /// Returns the walkable cell closest to the given point, searching outwards
+    /// from the cell it is in.
+    fn nearest_walkable_cell(&self, point: Vector) -> Option<Cell> {
+        const MAX_SEARCH_RADIUS: isize = 8;
+
+        let clamped = Vector::new(
+            point.x.clamp(
+                self.origin.x,
+                self.origin.x + self.width as Scalar * self.cell_size - 0.001,
+            ),
+            point.y,
+            point.z.clamp(
+                self.origin.y,
+                self.origin.y + self.depth as Scalar * self.cell_size - 0.001,
+            ),
+        );
+        let (x, z) = self.cell_at(clamped)?;
+
+        (0..=MAX_SEARCH_RADIUS).find_map(|radius| {
+            (-radius..=radius)
+                .flat_map(|dx| (-radius..=radius).map(move |dz| (dx, dz)))
+                .filter(|(dx, dz)| dx.abs() == radius || dz.abs() == radius)
+                .filter_map(|(dx, dz)| self.offset_cell((x, z), dx, dz))
+                .filter_map(|cell| Some((cell, self.cell_position(cell)?)))
+                .min_by(|(_, a), (_, b)| {
+                    a.distance_squared(point)
+                        .total_cmp(&b.distance_squared(point))
+                })
+                .map(|(cell, _)| cell)
+        })
+    }
+
+    fn offset_cell(&self, (x, z): Cell, dx: isize, dz: isize) -> Option<Cell> {
+        let x = x.checked_add_signed(dx)?;
+        let z = z.checked_add_signed(dz)?;
+        (x < self.width && z < self.depth).then_some((x, z))
+    }
+
+    /// Returns the walkable cells reachable from a cell in a single step.
+    ///
+    /// Diagonal moves are only allowed when both adjacent cells are walkable,
+    /// so paths don't cut across corners.
+    fn neighbors(&self, cell: Cell) -> impl Iterator<Item = Cell> + '_ {
+        let height = self.height(cell);
+
+        [
+            (-1, 0),
+            (1, 0),
+            (0, -1),
+            (0, 1),
+            (-1, -1),
+            (-1, 1),
+            (1, -1),
+            (1, 1),
+        ]
+        .into_iter()
+        .filter_map(move |(dx, dz)| {
+            let neighbor = self.offset_cell(cell, dx, dz)?;
+            let is_connected = |other: Cell| match (height, self.height(other)) {
+                (Some(a), Some(b)) => (a - b).abs() <= self.max_step_height,
+                _ => false,
+            };
+
+            let is_diagonal = dx != 0 && dz != 0;
+            let corners_clear = !is_diagonal
+                || (self
+                    .offset_cell(cell, dx, 0)
+                    .is_some_and(|corner| is_connected(corner))
+                    && self
+                        .offset_cell(cell, 0, dz)
+                        .is_some_and(|corner| is_connected(corner)));
+
+            (corners_clear && is_connected(neighbor)).then_some(neighbor)
+        })
+    }
+
+    /// Finds a path over walkable ground between two points using A*.
+    ///
+    /// The returned waypoints start at the cell nearest to `start` and end at the
+    /// cell nearest to `goal`, with waypoints along straight lines removed.
+    /// Returns `None` if the mesh isn't baked or the goal can't be reached.
+    pub fn find_path(&self, start: Vector, goal: Vector) -> Option<Vec<Vector>> {
+        if !self.is_baked() {
+            return None;
+        }
+
+        let start = self.nearest_walkable_cell(start)?;
+        let goal = self.nearest_walkable_cell(goal)?;
+        let goal_position = self.cell_position(goal)?;
+
+        let mut open = BinaryHeap::new();
+        let mut came_from = HashMap::<Cell, Cell>::default();
+        let mut cost = HashMap::<Cell, Scalar>::default();
+
+        cost.insert(start, 0.0);
+        open.push(OpenCell {
+            cell: start,
+            estimate: self.cell_position(start)?.distance(goal_position),
+        });
+
+        while let Some(OpenCell { cell, .. }) = open.pop() {
+            if cell == goal {
+                return Some(self.reconstruct_path(&came_from, goal));
+            }
+
+            let position = self.cell_position(cell)?;
+            let current_cost = cost[&cell];
+
+            for neighbor in self.neighbors(cell) {
+                let neighbor_position = self.cell_position(neighbor)?;
+                let new_cost = current_cost + position.distance(neighbor_position);
+
+                if cost.get(&neighbor).map_or(true, |&old| new_cost < old) {
+                    cost.insert(neighbor, new_cost);
+                    came_from.insert(neighbor, cell);
+                    open.push(OpenCell {
+                        cell: neighbor,
+                        estimate: new_cost + neighbor_position.distance(goal_position),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    fn reconstruct_path(&self, came_from: &HashMap<Cell, Cell>, goal: Cell) -> Vec<Vector> {
+        let mut cells = vec![goal];
+        while let Some(&previous) = came_from.get(cells.last().unwrap()) {
+            cells.push(previous);
+        }
+        cells.reverse();
+
+        let mut path: Vec<Vector> = cells
+            .into_iter()
+            .filter_map(|cell| self.cell_position(cell))
+            .collect();
+
+        // Drop waypoints that lie on a straight line between their neighbours
+        let mut i = 1;
+        while i + 1 < path.len() {
+            let incoming = (path[i] - path[i - 1]).normalize_or_zero();
+            let outgoing = (path[i + 1] - path[i]).normalize_or_zero();
+            if incoming.dot(outgoing) > 0.999 {
+                path.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        path
+    }
+}
+
+/// An entry in the A* open set, ordered so that the lowest estimate is popped first.
+struct OpenCell {
+    cell: Cell,
+    estimate: Scalar,
+}
+
+impl PartialEq for OpenCell {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for OpenCell {}
+
+impl PartialOrd for OpenCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// Updates the [`NavMeshSettings`] when the player's [`CharacterConfig`] is loaded
+/// or modified. The new settings are used by the next bake.
+fn match_player_character(
+    game_assets: Res<GameAssets>,
+    configs: Res<Assets<CharacterConfig>>,
+    mut settings: ResMut<NavMeshSettings>,
+) {
+    if let Some(config) = configs.get(&game_assets.character_config) {
+        settings.match_character(config);
+    }
+}
+
+/// The static level geometry a [`NavMesh`] is baked from.
+#[derive(SystemParam)]
+pub struct NavMeshGeometry<'w, 's> {
+    spatial_query: SpatialQuery<'w, 's>,
+    colliders: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static ColliderParent,
+            &'static ColliderAabb,
+            Has<Sensor>,
+        ),
+    >,
+    rigid_bodies: Query<'w, 's, &'static RigidBody>,
+}
+
+/// Bakes the [`NavMesh`] once the level's colliders have been generated.
+///
+/// The colliders are created from the level's meshes by [`AsyncCollider`], which removes
+/// itself when it's done. Baking waits one more frame after that so the new colliders
+/// have been added to the spatial query pipeline by the physics step.
+fn bake_nav_mesh(
+    mut removed_async_colliders: RemovedComponents<AsyncCollider>,
+    mut pending: Local<bool>,
+    settings: Res<NavMeshSettings>,
+    mut nav_mesh: ResMut<NavMesh>,
+    geometry: NavMeshGeometry,
+) {
+    // A level creates many colliders at once, which only need a single bake
+    let colliders_created = removed_async_colliders.read().count() > 0;
+    if *pending {
+        *pending = false;
+    } else {
+        *pending = colliders_created;
+        return;
+    }
+
+    *nav_mesh = NavMesh::bake(&geometry, &settings);
+
+    info!(
+        "Baked navigation mesh with {} walkable cells",
+        nav_mesh.heights.iter().flatten().count()
+    );
+}
+
+/// Toggle the navigation mesh overlay when pressing F10
+fn toggle_nav_mesh_debug(mut debug: ResMut<NavMeshDebug>, kbd: Res<Input<KeyCode>>) {
+    if kbd.just_pressed(KeyCode::F10) {
+        debug.0 = !debug.0;
+    }
+}
+
+/// Draws the connections between walkable cells.
+fn draw_nav_mesh(debug: Res<NavMeshDebug>, nav_mesh: Res<NavMesh>, mut gizmos: Gizmos) {
+    if !debug.0 || !nav_mesh.is_baked() {
+        return;
+    }
+
+    // Lift the lines slightly so they aren't hidden inside the ground
+    let offset = Vector::Y * 0.05;
+
+    for z in 0..nav_mesh.depth {
+        for x in 0..nav_mesh.width {
+            let Some(position) = nav_mesh.cell_position((x, z)) else {
+                continue;
+            };
+
+            // Only draw towards cells with a larger index so every edge is drawn once
+            for neighbor in nav_mesh
+                .neighbors((x, z))
+                .filter(|&(nx, nz)| (nz, nx) > (z, x))
+            {
+                if let Some(neighbor_position) = nav_mesh.cell_position(neighbor) {
+                    gizmos.line(position + offset, neighbor_position + offset, Color::GREEN);
+                }
+            }
+        }
+    }
+}
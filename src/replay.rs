@@ -0,0 +1,535 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use bevy::prelude::*;
+use bevy_xpbd_3d::{math::*, prelude::*, PhysicsSchedule, PhysicsStepSet};
+use leafwing_input_manager::prelude::*;
+
+use crate::{
+    character::{
+        CharacterController, CharacterState, CharacterStateQuery, ControllerSet, MovementIntent,
+        MovementMode, PlayerAction, PlayerId,
+    },
+    AppState,
+};
+
+const REPLAY_PATH: &str = "replays/latest.replay";
+
+const MAGIC: &[u8; 4] = b"RPLY";
+const VERSION: u8 = 2;
+
+const JUMP_PRESSED: u8 = 1 << 0;
+const JUMP_RELEASED: u8 = 1 << 1;
+
+const GROUNDED: u8 = 1 << 0;
+const JUMPING: u8 = 1 << 1;
+
+/// Records the players' input on every physics tick and plays it back.
+///
+/// Press F5 to start and stop recording, and F6 to replay the last recording.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhysicsTick>()
+            .init_resource::<ReplayMode>()
+            .add_systems(Update, toggle_replay.run_if(in_state(AppState::Main)))
+            .add_systems(
+                PhysicsSchedule,
+                (count_ticks, record_inputs, play_inputs)
+                    .chain()
+                    .before(ControllerSet)
+                    .before(PhysicsStepSet::BroadPhase)
+                    .run_if(in_state(AppState::Main)),
+            )
+            .add_systems(
+                PhysicsSchedule,
+                (check_positions, start_replay)
+                    .chain()
+                    .after(PhysicsStepSet::Sleeping)
+                    .before(PhysicsStepSet::SpatialQuery)
+                    .run_if(in_state(AppState::Main)),
+            );
+    }
+}
+
+/// The number of physics ticks that have run so far.
+#[derive(Resource, Default)]
+pub struct PhysicsTick(pub u64);
+
+/// One player's input on a single physics tick.
+///
+/// The movement direction is stored after it has been made camera-relative,
+/// so replays don't depend on where the camera was looking.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TickInput {
+    /// A bit for every held [`PlayerAction`], in declaration order.
+    pub held: u16,
+    pub jump_pressed: bool,
+    pub jump_released: bool,
+    pub direction: Vector,
+}
+
+impl TickInput {
+    fn is_held(&self, action: PlayerAction) -> bool {
+        self.held & action_bit(action) != 0
+    }
+}
+
+/// The input of all players on a single physics tick, and a checksum
+/// of their positions after the tick.
+#[derive(Clone, Debug)]
+pub struct ReplayFrame {
+    /// The tick, counted from the start of the recording.
+    pub tick: u32,
+    pub inputs: Vec<TickInput>,
+    pub checksum: u64,
+}
+
+/// A recording of the players' input, stored in a compact binary file.
+#[derive(Clone, Debug, Default)]
+pub struct Replay {
+    /// The controller state of every player when the recording started.
+    ///
+    /// The body a player stands on is stored as an [`Entity`], so it's only
+    /// restored correctly within the session the replay was recorded in.
+    pub start: Vec<CharacterState>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the replay to a file, creating its directory if needed.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, self.start.len() as u8])?;
+
+        for state in &self.start {
+            write_state(writer, state)?;
+        }
+
+        writer.write_all(&(self.frames.len() as u32).to_le_bytes())?;
+        for frame in &self.frames {
+            writer.write_all(&frame.tick.to_le_bytes())?;
+            writer.write_all(&frame.checksum.to_le_bytes())?;
+
+            for input in &frame.inputs {
+                let mut flags = 0;
+                if input.jump_pressed {
+                    flags |= JUMP_PRESSED;
+                }
+                if input.jump_released {
+                    flags |= JUMP_RELEASED;
+                }
+
+                writer.write_all(&input.held.to_le_bytes())?;
+                writer.write_all(&[flags])?;
+                write_vector(writer, input.direction)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let [version, players] = read_bytes(reader)?;
+        if &magic != MAGIC || version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a replay file of a supported version",
+            ));
+        }
+
+        let start = (0..players)
+            .map(|_| read_state(reader))
+            .collect::<io::Result<_>>()?;
+
+        let frame_count = u32::from_le_bytes(read_bytes(reader)?);
+        let frames = (0..frame_count)
+            .map(|_| {
+                let tick = u32::from_le_bytes(read_bytes(reader)?);
+                let checksum = u64::from_le_bytes(read_bytes(reader)?);
+
+                let inputs = (0..players)
+                    .map(|_| {
+                        let held = u16::from_le_bytes(read_bytes(reader)?);
+                        let [flags] = read_bytes(reader)?;
+                        Ok(TickInput {
+                            held,
+                            jump_pressed: flags & JUMP_PRESSED != 0,
+                            jump_released: flags & JUMP_RELEASED != 0,
+                            direction: read_vector(reader)?,
+                        })
+                    })
+                    .collect::<io::Result<_>>()?;
+
+                Ok(ReplayFrame {
+                    tick,
+                    inputs,
+                    checksum,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self { start, frames })
+    }
+}
+
+/// Whether input is currently being recorded or played back.
+///
+/// Recording and playback start at the end of a physics tick, so the players'
+/// state is captured and restored in between two ticks.
+#[derive(Resource, Default)]
+pub enum ReplayMode {
+    #[default]
+    Idle,
+    /// Starts recording at the end of the current physics tick.
+    StartRecording,
+    Recording {
+        replay: Replay,
+        start_tick: u64,
+    },
+    /// Restores the players' starting state at the end of the current
+    /// physics tick and starts playing back.
+    StartPlaying {
+        replay: Replay,
+    },
+    Playing {
+        replay: Replay,
+        frame: usize,
+        diverged: bool,
+    },
+}
+
+/// Hashes the positions of the characters, in order, to detect when a replay
+/// doesn't reproduce the recording anymore.
+pub fn position_checksum<'a>(positions: impl IntoIterator<Item = &'a Vector>) -> u64 {
+    // FNV-1a, which unlike the standard hasher is guaranteed to stay the same
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    positions
+        .into_iter()
+        .flat_map(|position| position.to_array())
+        .flat_map(|value| value.to_le_bytes())
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(PRIME)
+        })
+}
+
+fn action_bit(action: PlayerAction) -> u16 {
+    let index = PlayerAction::variants()
+        .position(|variant| variant == action)
+        .unwrap();
+    1 << index
+}
+
+fn write_vector(writer: &mut impl Write, vector: Vector) -> io::Result<()> {
+    for value in vector.to_array() {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_vector(reader: &mut impl Read) -> io::Result<Vector> {
+    let mut values = [0.0; 3];
+    for value in &mut values {
+        *value = Scalar::from_le_bytes(read_bytes(reader)?);
+    }
+    Ok(Vector::from_array(values))
+}
+
+fn write_state(writer: &mut impl Write, state: &CharacterState) -> io::Result<()> {
+    write_vector(writer, state.position)?;
+    for value in state.rotation.to_array() {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    write_vector(writer, state.linear_velocity)?;
+
+    let mode = match state.mode {
+        MovementMode::Run => 0,
+        MovementMode::Walk => 1,
+        MovementMode::Sprint => 2,
+        MovementMode::Crouch => 3,
+    };
+    let mut flags = 0;
+    if state.grounded_on.is_some() {
+        flags |= GROUNDED;
+    }
+    if state.jumping {
+        flags |= JUMPING;
+    }
+    writer.write_all(&[mode, flags])?;
+
+    writer.write_all(&state.coyote_time.to_le_bytes())?;
+    writer.write_all(&state.jump_buffer.to_le_bytes())?;
+    writer.write_all(&state.air_jumps_remaining.to_le_bytes())?;
+    let ground = state.grounded_on.map_or(0, Entity::to_bits);
+    writer.write_all(&ground.to_le_bytes())
+}
+
+fn read_state(reader: &mut impl Read) -> io::Result<CharacterState> {
+    let position = read_vector(reader)?;
+    let mut rotation = [0.0; 4];
+    for value in &mut rotation {
+        *value = Scalar::from_le_bytes(read_bytes(reader)?);
+    }
+    let linear_velocity = read_vector(reader)?;
+
+    let [mode, flags] = read_bytes(reader)?;
+    let mode = match mode {
+        0 => MovementMode::Run,
+        1 => MovementMode::Walk,
+        2 => MovementMode::Sprint,
+        3 => MovementMode::Crouch,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unknown movement mode",
+            ))
+        }
+    };
+
+    let coyote_time = f32::from_le_bytes(read_bytes(reader)?);
+    let jump_buffer = f32::from_le_bytes(read_bytes(reader)?);
+    let air_jumps_remaining = u32::from_le_bytes(read_bytes(reader)?);
+    let ground = u64::from_le_bytes(read_bytes(reader)?);
+
+    Ok(CharacterState {
+        position,
+        rotation: Quaternion::from_array(rotation),
+        linear_velocity,
+        mode,
+        coyote_time,
+        jump_buffer,
+        air_jumps_remaining,
+        grounded_on: (flags & GROUNDED != 0).then(|| Entity::from_bits(ground)),
+        jumping: flags & JUMPING != 0,
+    })
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Starts and stops recording, and starts playing back the last recording
+/// from the players' recorded starting state.
+fn toggle_replay(
+    kbd: Res<Input<KeyCode>>,
+    mut mode: ResMut<ReplayMode>,
+    mut toggle_actions: ResMut<ToggleActions<PlayerAction>>,
+    players: Query<(), (With<PlayerId>, With<CharacterController>)>,
+) {
+    if kbd.just_pressed(KeyCode::F5) {
+        match std::mem::take(&mut *mode) {
+            ReplayMode::Recording { replay, .. } => match replay.save(REPLAY_PATH) {
+                Ok(()) => info!(
+                    "Saved a replay of {} ticks to {REPLAY_PATH}",
+                    replay.frames.len()
+                ),
+                Err(error) => error!("Failed to save the replay to {REPLAY_PATH}: {error}"),
+            },
+            ReplayMode::Idle => {
+                info!("Recording a replay");
+                *mode = ReplayMode::StartRecording;
+            }
+            other => *mode = other,
+        }
+    }
+
+    if kbd.just_pressed(KeyCode::F6) && matches!(*mode, ReplayMode::Idle) {
+        let replay = match Replay::load(REPLAY_PATH) {
+            Ok(replay) => replay,
+            Err(error) => {
+                warn!("Failed to load the replay from {REPLAY_PATH}: {error}");
+                return;
+            }
+        };
+
+        let player_count = players.iter().count();
+        if player_count != replay.start.len() {
+            warn!(
+                "The replay was recorded with {} players, but there are {player_count}",
+                replay.start.len(),
+            );
+            return;
+        }
+
+        // Device input is ignored until the replay has finished
+        toggle_actions.enabled = false;
+
+        info!("Playing a replay of {} ticks", replay.frames.len());
+        *mode = ReplayMode::StartPlaying { replay };
+    }
+}
+
+/// Captures or restores the players' [`CharacterState`] when a recording or playback
+/// starts. This runs after the players have moved but before the ground casters
+/// are updated, so the state is the same as at the start of the next tick.
+fn start_replay(
+    mut commands: Commands,
+    tick: Res<PhysicsTick>,
+    mut mode: ResMut<ReplayMode>,
+    mut players: Query<(&PlayerId, CharacterStateQuery)>,
+) {
+    if !matches!(
+        *mode,
+        ReplayMode::StartRecording | ReplayMode::StartPlaying { .. }
+    ) {
+        return;
+    }
+
+    let mut players = players.iter_mut().collect::<Vec<_>>();
+    players.sort_by_key(|(player, _)| player.0);
+
+    match std::mem::take(&mut *mode) {
+        ReplayMode::StartRecording => {
+            *mode = ReplayMode::Recording {
+                replay: Replay {
+                    start: players
+                        .iter()
+                        .map(|(_, character)| character.state())
+                        .collect(),
+                    frames: Vec::new(),
+                },
+                start_tick: tick.0,
+            };
+        }
+        ReplayMode::StartPlaying { replay } => {
+            for ((_, character), start) in players.iter_mut().zip(&replay.start) {
+                character.restore(start, &mut commands);
+            }
+            *mode = ReplayMode::Playing {
+                replay,
+                frame: 0,
+                diverged: false,
+            };
+        }
+        other => *mode = other,
+    }
+}
+
+fn count_ticks(mut tick: ResMut<PhysicsTick>) {
+    tick.0 += 1;
+}
+
+fn record_inputs(
+    tick: Res<PhysicsTick>,
+    mut mode: ResMut<ReplayMode>,
+    players: Query<(&PlayerId, &ActionState<PlayerAction>, &MovementIntent)>,
+) {
+    let ReplayMode::Recording { replay, start_tick } = &mut *mode else {
+        return;
+    };
+
+    let mut players = players.iter().collect::<Vec<_>>();
+    players.sort_by_key(|(player, _, _)| player.0);
+
+    let inputs = players
+        .into_iter()
+        .map(|(_, action_state, intent)| TickInput {
+            held: PlayerAction::variants()
+                .filter(|action| action_state.pressed(*action))
+                .fold(0, |held, action| held | action_bit(action)),
+            jump_pressed: intent.jump_pressed,
+            jump_released: intent.jump_released,
+            direction: intent.direction,
+        })
+        .collect();
+
+    replay.frames.push(ReplayFrame {
+        tick: (tick.0 - *start_tick) as u32,
+        inputs,
+        // Filled in once the tick has been simulated
+        checksum: 0,
+    });
+}
+
+/// Overwrites the players' movement intents with the recorded input,
+/// in place of the input sampled from their devices.
+fn play_inputs(mode: Res<ReplayMode>, mut players: Query<(&PlayerId, &mut MovementIntent)>) {
+    let ReplayMode::Playing { replay, frame, .. } = &*mode else {
+        return;
+    };
+    let Some(frame) = replay.frames.get(*frame) else {
+        return;
+    };
+
+    let mut players = players.iter_mut().collect::<Vec<_>>();
+    players.sort_by_key(|(player, _)| player.0);
+
+    for ((_, mut intent), input) in players.into_iter().zip(&frame.inputs) {
+        *intent = MovementIntent {
+            direction: input.direction,
+            jump_pressed: input.jump_pressed,
+            jump_released: input.jump_released,
+            sprint: input.is_held(PlayerAction::Sprint),
+            crouch: input.is_held(PlayerAction::Crouch),
+            walk: input.is_held(PlayerAction::Walk),
+        };
+    }
+}
+
+/// Stores the position checksum of each recorded tick, and compares it
+/// against the recording while playing back.
+fn check_positions(
+    mut mode: ResMut<ReplayMode>,
+    mut toggle_actions: ResMut<ToggleActions<PlayerAction>>,
+    players: Query<(&PlayerId, &Position)>,
+) {
+    let mut players = players.iter().collect::<Vec<_>>();
+    players.sort_by_key(|(player, _)| player.0);
+    let checksum = position_checksum(players.iter().map(|(_, position)| &position.0));
+
+    match &mut *mode {
+        ReplayMode::Idle | ReplayMode::StartRecording | ReplayMode::StartPlaying { .. } => {}
+        ReplayMode::Recording { replay, .. } => {
+            if let Some(frame) = replay.frames.last_mut() {
+                frame.checksum = checksum;
+            }
+        }
+        ReplayMode::Playing {
+            replay,
+            frame,
+            diverged,
+        } => {
+            if let Some(recorded) = replay.frames.get(*frame) {
+                if !*diverged && recorded.checksum != checksum {
+                    warn!(
+                        "The replay diverged from the recording at tick {}",
+                        recorded.tick
+                    );
+                    *diverged = true;
+                }
+                *frame += 1;
+            }
+
+            if *frame >= replay.frames.len() {
+                info!("Finished playing the replay");
+                toggle_actions.enabled = true;
+                *mode = ReplayMode::Idle;
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+
+use crate::{
+    character::PlayerId,
+    inventory::{Inventory, ItemDefinition},
+};
+
+const SLOT_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+const SELECTED_SLOT_COLOR: Color = Color::rgba(0.8, 0.6, 0.1, 0.8);
+
+#[derive(Component)]
+struct HotbarRoot;
+
+/// A hotbar slot showing the inventory slot with the same index.
+#[derive(Component)]
+struct HotbarSlot(usize);
+
+pub struct HotbarPlugin;
+
+impl Plugin for HotbarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_hotbar)
+            .add_systems(Update, update_hotbar);
+    }
+}
+
+fn setup_hotbar(mut commands: Commands) {
+    commands.spawn((
+        HotbarRoot,
+        NodeBundle {
+            // placed to the right of the FPS counter
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(10.),
+                left: Val::Percent(20.),
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(4.0),
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+/// Mirrors the first player's inventory in the hotbar, creating the slots
+/// the first time the inventory is seen.
+fn update_hotbar(
+    mut commands: Commands,
+    items: Res<Assets<ItemDefinition>>,
+    players: Query<(&PlayerId, &Inventory)>,
+    root: Query<Entity, With<HotbarRoot>>,
+    mut slots: Query<(&HotbarSlot, &mut BackgroundColor, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    let Some((_, inventory)) = players.iter().find(|(player, _)| player.0 == 0) else {
+        return;
+    };
+
+    if slots.is_empty() {
+        let root = root.single();
+        for index in 0..inventory.slots().len() {
+            let slot = commands
+                .spawn((
+                    HotbarSlot(index),
+                    NodeBundle {
+                        background_color: BackgroundColor(SLOT_COLOR),
+                        style: Style {
+                            width: Val::Px(64.0),
+                            height: Val::Px(40.0),
+                            padding: UiRect::all(Val::Px(4.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ))
+                .with_children(|slot| {
+                    slot.spawn(TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 12.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ));
+                })
+                .id();
+            commands.entity(root).add_child(slot);
+        }
+        return;
+    }
+
+    for (slot, mut background, children) in &mut slots {
+        let color = if slot.0 == inventory.selected() {
+            SELECTED_SLOT_COLOR
+        } else {
+            SLOT_COLOR
+        };
+        if background.0 != color {
+            background.0 = color;
+        }
+
+        let label = inventory.slots()[slot.0]
+            .as_ref()
+            .and_then(|stack| {
+                let item = items.get(&stack.item)?;
+                Some(if stack.count > 1 {
+                    format!("{} x{}", item.name, stack.count)
+                } else {
+                    item.name.clone()
+                })
+            })
+            .unwrap_or_default();
+
+        let mut texts = texts.iter_many_mut(children);
+        while let Some(mut text) = texts.fetch_next() {
+            if text.sections[0].value != label {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
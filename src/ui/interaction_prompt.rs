@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+
+use crate::{
+    character::PlayerId,
+    interaction::{Interactable, InteractionTarget},
+};
+
+#[derive(Component)]
+struct PromptRoot;
+
+#[derive(Component)]
+struct PromptText;
+
+pub struct InteractionPromptPlugin;
+
+impl Plugin for InteractionPromptPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_interaction_prompt)
+            .add_systems(Update, update_interaction_prompt);
+    }
+}
+
+fn setup_interaction_prompt(mut commands: Commands) {
+    commands
+        .spawn((
+            PromptRoot,
+            NodeBundle {
+                background_color: BackgroundColor(Color::BLACK.with_a(0.5)),
+                visibility: Visibility::Hidden,
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Percent(20.),
+                    left: Val::Percent(45.),
+                    padding: UiRect::all(Val::Px(6.0)),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|root| {
+            root.spawn((
+                PromptText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
+
+/// Shows the prompt of the interactable the first player is targeting.
+fn update_interaction_prompt(
+    players: Query<(&PlayerId, Option<&InteractionTarget>)>,
+    interactables: Query<&Interactable>,
+    mut root: Query<&mut Visibility, With<PromptRoot>>,
+    mut text: Query<&mut Text, With<PromptText>>,
+) {
+    let prompt = players
+        .iter()
+        .find(|(player, _)| player.0 == 0)
+        .and_then(|(_, target)| target)
+        .and_then(|target| interactables.get(target.0).ok())
+        .map(|interactable| interactable.prompt.as_str());
+
+    let mut visibility = root.single_mut();
+    match prompt {
+        Some(prompt) => {
+            *visibility = Visibility::Visible;
+            let mut text = text.single_mut();
+            if text.sections[0].value != prompt {
+                text.sections[0].value = prompt.to_string();
+            }
+        }
+        None => *visibility = Visibility::Hidden,
+    }
+}
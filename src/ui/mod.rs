@@ -1 +1,4 @@
 pub mod fps_counter;
+pub mod hotbar;
+pub mod interaction_prompt;
+pub mod rebind_menu;
@@ -0,0 +1,243 @@
+use bevy::prelude::*;
+use leafwing_input_manager::{prelude::*, user_input::InputKind};
+
+use crate::{bindings::InputBindings, character::PlayerAction};
+
+#[derive(Component)]
+struct RebindMenuRoot;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BindingDevice {
+    KeyboardMouse,
+    Gamepad,
+}
+
+/// A button that rebinds an action on one kind of device when clicked.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+struct RebindButton {
+    action: PlayerAction,
+    device: BindingDevice,
+}
+
+/// The binding that is waiting for the next key or button press.
+#[derive(Resource, Default)]
+struct PendingRebind(Option<RebindButton>);
+
+pub struct RebindMenuPlugin;
+
+impl Plugin for RebindMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingRebind>()
+            .add_systems(Startup, setup_rebind_menu)
+            .add_systems(
+                Update,
+                (
+                    rebind_menu_showhide,
+                    start_rebind,
+                    capture_rebind,
+                    update_binding_labels,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn setup_rebind_menu(mut commands: Commands) {
+    let text_style = TextStyle {
+        font_size: 16.0,
+        color: Color::WHITE,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            RebindMenuRoot,
+            NodeBundle {
+                background_color: BackgroundColor(Color::BLACK.with_a(0.8)),
+                z_index: ZIndex::Global(i32::MAX - 1),
+                visibility: Visibility::Hidden,
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(20.),
+                    left: Val::Percent(30.),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(12.0)),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "Controls (F1 to close, Esc to cancel)",
+                text_style.clone(),
+            ));
+
+            // Movement uses a directional input, which can't be captured from a single press
+            for action in PlayerAction::variants().filter(|action| *action != PlayerAction::Run) {
+                root.spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|row| {
+                    row.spawn(TextBundle {
+                        text: Text::from_section(format!("{action:?}"), text_style.clone()),
+                        style: Style {
+                            width: Val::Px(100.0),
+                            ..default()
+                        },
+                        ..default()
+                    });
+
+                    for device in [BindingDevice::KeyboardMouse, BindingDevice::Gamepad] {
+                        row.spawn((
+                            RebindButton { action, device },
+                            ButtonBundle {
+                                background_color: BackgroundColor(Color::DARK_GRAY),
+                                style: Style {
+                                    width: Val::Px(160.0),
+                                    padding: UiRect::all(Val::Px(4.0)),
+                                    justify_content: JustifyContent::Center,
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                        ))
+                        .with_children(|button| {
+                            button.spawn(TextBundle::from_section("", text_style.clone()));
+                        });
+                    }
+                });
+            }
+        });
+}
+
+/// Toggle the rebinding menu when pressing F1
+///
+/// Player input is disabled while the menu is open, so the presses
+/// captured for rebinding don't also control the character.
+fn rebind_menu_showhide(
+    mut q: Query<&mut Visibility, With<RebindMenuRoot>>,
+    mut pending: ResMut<PendingRebind>,
+    mut toggle_actions: ResMut<ToggleActions<PlayerAction>>,
+    kbd: Res<Input<KeyCode>>,
+) {
+    if kbd.just_pressed(KeyCode::F1) {
+        let mut vis = q.single_mut();
+        *vis = match *vis {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+        toggle_actions.enabled = *vis == Visibility::Hidden;
+        pending.0 = None;
+    }
+}
+
+fn start_rebind(
+    buttons: Query<(&Interaction, &RebindButton), Changed<Interaction>>,
+    mut pending: ResMut<PendingRebind>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction == Interaction::Pressed {
+            pending.0 = Some(*button);
+        }
+    }
+}
+
+/// Binds the next key or button press to the pending action and saves the bindings.
+fn capture_rebind(
+    mut pending: ResMut<PendingRebind>,
+    mut bindings: ResMut<InputBindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+) {
+    // Skip the frame the rebind started on, so the click on the button isn't captured
+    if pending.is_changed() {
+        return;
+    }
+    let Some(target) = pending.0 else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Escape) {
+        pending.0 = None;
+        return;
+    }
+
+    let input = match target.device {
+        BindingDevice::KeyboardMouse => keys
+            .get_just_pressed()
+            .next()
+            .map(|key| InputKind::Keyboard(*key))
+            .or_else(|| {
+                mouse_buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| InputKind::Mouse(*button))
+            }),
+        BindingDevice::Gamepad => gamepad_buttons
+            .get_just_pressed()
+            .next()
+            .map(|button| InputKind::GamepadButton(button.button_type)),
+    };
+
+    let Some(input) = input else {
+        return;
+    };
+
+    let device_bindings = match target.device {
+        BindingDevice::KeyboardMouse => &mut bindings.keyboard_mouse,
+        BindingDevice::Gamepad => &mut bindings.gamepad,
+    };
+    device_bindings.insert(target.action, vec![UserInput::Single(input)]);
+
+    bindings.save_to_settings();
+    pending.0 = None;
+}
+
+fn update_binding_labels(
+    bindings: Res<InputBindings>,
+    pending: Res<PendingRebind>,
+    buttons: Query<(&RebindButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !bindings.is_changed() && !pending.is_changed() {
+        return;
+    }
+
+    for (button, children) in &buttons {
+        let label = if pending.0 == Some(*button) {
+            "Press a button...".to_string()
+        } else {
+            let device_bindings = match button.device {
+                BindingDevice::KeyboardMouse => &bindings.keyboard_mouse,
+                BindingDevice::Gamepad => &bindings.gamepad,
+            };
+            device_bindings
+                .get(&button.action)
+                .and_then(|inputs| inputs.first())
+                .map_or_else(|| "-".to_string(), binding_label)
+        };
+
+        let mut texts = texts.iter_many_mut(children);
+        while let Some(mut text) = texts.fetch_next() {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+fn binding_label(input: &UserInput) -> String {
+    match input {
+        UserInput::Single(InputKind::Keyboard(key)) => format!("{key:?}"),
+        UserInput::Single(InputKind::Mouse(button)) => format!("Mouse {button:?}"),
+        UserInput::Single(InputKind::GamepadButton(button)) => format!("{button:?}"),
+        other => format!("{other:?}"),
+    }
+}
@@ -0,0 +1,324 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::math::*;
+use holder::{
+    character::{CharacterConfig, PlayerAction},
+    harness::SimulationHarness,
+};
+
+/// Half the height of the player capsule, i.e. its center's height when standing on the floor.
+const STANDING_HEIGHT: Scalar = 0.625 + 0.2;
+
+fn landed_character() -> (SimulationHarness, Entity) {
+    landed_character_with(&CharacterConfig::default())
+}
+
+fn landed_character_with(config: &CharacterConfig) -> (SimulationHarness, Entity) {
+    let mut harness = SimulationHarness::new();
+    harness.spawn_floor();
+    let character = harness.spawn_character_with(Vector::Y * 2.0, config);
+    harness.step(120);
+    (harness, character)
+}
+
+#[test]
+fn character_falls_onto_the_floor() {
+    let (harness, character) = landed_character();
+
+    assert!(harness.is_grounded(character));
+    let height = harness.position(character).y;
+    assert!(
+        (height - STANDING_HEIGHT).abs() < 0.1,
+        "character should stand on the floor, but is at y = {height}"
+    );
+}
+
+#[test]
+fn character_runs_forward() {
+    let (mut harness, character) = landed_character();
+    let start = harness.position(character);
+
+    harness.set_movement(character, Vec2::Y);
+    harness.step(60);
+
+    let end = harness.position(character);
+    assert!(harness.is_grounded(character));
+    assert!(start.z - end.z > 1.0, "moved from {start} to {end}");
+    assert!((start.x - end.x).abs() < 0.01);
+}
+
+#[test]
+fn character_jumps_and_lands() {
+    let (mut harness, character) = landed_character();
+
+    harness.press(character, PlayerAction::Jump);
+    harness.step(10);
+
+    assert!(!harness.is_grounded(character));
+    assert!(harness.position(character).y > STANDING_HEIGHT + 0.3);
+
+    harness.release(character, PlayerAction::Jump);
+    harness.step(120);
+
+    assert!(harness.is_grounded(character));
+    assert!((harness.position(character).y - STANDING_HEIGHT).abs() < 0.1);
+}
+
+#[test]
+fn movement_is_relative_to_the_camera() {
+    let (mut harness, character) = landed_character();
+    // Looking down at the character along world-space +X
+    harness.spawn_camera(
+        character,
+        Transform::from_xyz(-5.0, 3.0, 0.0).looking_at(Vec3::Y, Vec3::Y),
+    );
+    // Let the camera's global transform be propagated
+    harness.step(1);
+
+    harness.set_movement(character, Vec2::Y);
+    harness.step(30);
+
+    let velocity = harness.velocity(character);
+    assert!(velocity.x > 1.0, "forward isn't +X, velocity is {velocity}");
+    assert!(velocity.z.abs() < 0.01);
+
+    // The camera's right is world-space +Z
+    harness.set_movement(character, Vec2::X);
+    harness.step(60);
+
+    let velocity = harness.velocity(character);
+    assert!(velocity.z > 1.0, "right isn't +Z, velocity is {velocity}");
+    assert!(velocity.x.abs() < 0.1);
+}
+
+/// Lands, runs forward until reaching top speed and lets go of the stick for a quarter
+/// of a second, returning the velocities at top speed and at the end.
+fn run_and_stop(tick_rate: Scalar) -> (Vector, Vector) {
+    let ticks = |seconds: Scalar| (seconds * tick_rate).round() as usize;
+
+    let mut harness = SimulationHarness::with_tick_rate(tick_rate);
+    harness.spawn_floor();
+    let character = harness.spawn_character(Vector::Y * 2.0);
+    harness.step(ticks(2.0));
+
+    harness.set_movement(character, Vec2::Y);
+    harness.step(ticks(1.0));
+    let top_velocity = harness.velocity(character);
+
+    harness.set_movement(character, Vec2::ZERO);
+    harness.step(ticks(0.25));
+
+    (top_velocity, harness.velocity(character))
+}
+
+#[test]
+fn movement_is_independent_of_the_tick_rate() {
+    let max_speed = CharacterConfig::default().max_speed;
+    let (slow_top, slow) = run_and_stop(60.0);
+    let (fast_top, fast) = run_and_stop(144.0);
+
+    for (top_velocity, tick_rate) in [(slow_top, 60), (fast_top, 144)] {
+        let top_speed = Vector::new(top_velocity.x, 0.0, top_velocity.z).length();
+        assert!(
+            (top_speed - max_speed).abs() < 0.01 * max_speed,
+            "top speed is {top_speed} at {tick_rate} Hz instead of {max_speed}"
+        );
+    }
+
+    assert!(slow.z < -0.5, "character didn't move, velocity is {slow}");
+    assert!(
+        (slow - fast).length() < 0.05 * slow.length(),
+        "velocity is {slow} at 60 Hz but {fast} at 144 Hz"
+    );
+}
+
+/// Drops a character onto a 50 degree ramp, steeper than the default max slope angle
+/// of 30 degrees, and returns its position after the given number of ticks.
+fn drop_onto_steep_ramp(config: &CharacterConfig, ticks: usize) -> Vector {
+    let mut harness = SimulationHarness::new();
+    harness.spawn_floor();
+    // The ramp faces +Z and reaches down to the floor
+    harness.spawn_rotated_box(
+        Vector::Y * 2.0,
+        Vector::new(4.0, 0.5, 6.0),
+        Quaternion::from_rotation_x(Scalar::to_radians(50.0)),
+    );
+    let character = harness.spawn_character_with(Vector::Y * 4.0, config);
+
+    harness.step(ticks);
+    harness.position(character)
+}
+
+#[test]
+fn characters_slide_off_steep_ramps() {
+    let position = drop_onto_steep_ramp(&CharacterConfig::default(), 180);
+    assert!(
+        position.z > 2.0 && position.y < 1.0,
+        "character didn't slide off the ramp, it's at {position}"
+    );
+
+    // Gravity alone pulls the character down the ramp too, just more slowly
+    let sliding = drop_onto_steep_ramp(&CharacterConfig::default(), 40);
+    let not_sliding = drop_onto_steep_ramp(
+        &CharacterConfig {
+            slope_slide_acceleration: 0.0,
+            ..default()
+        },
+        40,
+    );
+    assert!(
+        sliding.z > not_sliding.z + 0.3,
+        "slope sliding didn't speed up the slide, reached {sliding} instead of {not_sliding}"
+    );
+}
+
+#[test]
+fn characters_ride_moving_platforms() {
+    let mut harness = SimulationHarness::new();
+    let platform_velocity = Vector::X * 2.0;
+    let platform = harness.spawn_platform(
+        Vector::NEG_Y * 0.5,
+        Vector::new(4.0, 1.0, 4.0),
+        platform_velocity,
+    );
+    let character = harness.spawn_character(Vector::Y * 2.0);
+    harness.step(60);
+
+    let offset = harness.position(character) - harness.position(platform);
+    harness.step(30);
+
+    // The platform has moved a meter, and the character along with it
+    assert!(harness.is_grounded(character));
+    let new_offset = harness.position(character) - harness.position(platform);
+    assert!(
+        (new_offset - offset).length() < 0.05,
+        "character slipped from {offset} to {new_offset} relative to the platform"
+    );
+
+    harness.press(character, PlayerAction::Jump);
+    harness.step(2);
+
+    // Jumping off keeps the platform's momentum, minus a little damping
+    assert!(!harness.is_grounded(character));
+    let velocity = harness.velocity(character);
+    assert!(
+        velocity.x > platform_velocity.x * 0.8,
+        "character lost the platform's momentum, velocity is {velocity}"
+    );
+}
+
+#[test]
+fn walls_stop_the_character() {
+    let (mut harness, character) = landed_character();
+    harness.spawn_box(Vector::new(0.0, 1.0, -3.0), Vector::new(4.0, 2.0, 0.5));
+
+    harness.set_movement(character, Vec2::Y);
+    harness.step(180);
+
+    // The wall's front face is at z = -2.75 and the capsule has a radius of 0.2
+    let z = harness.position(character).z;
+    assert!(
+        z > -2.75 + 0.15,
+        "character went through the wall to z = {z}"
+    );
+}
+
+/// A config without air jumps, so only ground, coyote and buffered jumps are possible.
+fn grounded_jumps_only() -> CharacterConfig {
+    CharacterConfig {
+        air_jumps: 0,
+        ..default()
+    }
+}
+
+/// Runs a character off the edge of a ledge and returns it on the first airborne tick.
+fn run_off_ledge() -> (SimulationHarness, Entity) {
+    let mut harness = SimulationHarness::new();
+    // The ledge's edge is at z = -2, with nothing below it
+    harness.spawn_box(Vector::NEG_Y * 0.5, Vector::new(4.0, 1.0, 4.0));
+    let character = harness.spawn_character_with(Vector::Y * 2.0, &grounded_jumps_only());
+    harness.step(120);
+    assert!(harness.is_grounded(character));
+
+    harness.set_movement(character, Vec2::Y);
+    for _ in 0..120 {
+        harness.step(1);
+        if !harness.is_grounded(character) {
+            return (harness, character);
+        }
+    }
+    panic!("character never left the ledge");
+}
+
+#[test]
+fn late_jumps_use_coyote_time() {
+    let (mut harness, character) = run_off_ledge();
+
+    // The coyote time of the default config is 0.12 seconds, about 7 ticks
+    harness.step(3);
+    harness.press(character, PlayerAction::Jump);
+    harness.step(1);
+
+    let velocity = harness.velocity(character);
+    assert!(
+        velocity.y > 0.0,
+        "late jump didn't happen, velocity is {velocity}"
+    );
+}
+
+#[test]
+fn jumps_after_coyote_time_fail() {
+    let (mut harness, character) = run_off_ledge();
+
+    harness.step(20);
+    harness.press(character, PlayerAction::Jump);
+    harness.step(1);
+
+    let velocity = harness.velocity(character);
+    assert!(
+        velocity.y < 0.0,
+        "jumped in mid-air, velocity is {velocity}"
+    );
+}
+
+#[test]
+fn early_jumps_are_buffered() {
+    let mut harness = SimulationHarness::new();
+    harness.spawn_floor();
+    let character = harness.spawn_character_with(Vector::Y * 2.0, &grounded_jumps_only());
+
+    // Press jump while still falling, a few ticks before landing
+    while harness.position(character).y > STANDING_HEIGHT + 0.6 {
+        harness.step(1);
+    }
+    assert!(!harness.is_grounded(character));
+    harness.press(character, PlayerAction::Jump);
+
+    // The jump buffer of the default config is 0.15 seconds, about 9 ticks
+    let mut jumped = false;
+    for _ in 0..15 {
+        harness.step(1);
+        jumped |= harness.velocity(character).y > 0.0;
+    }
+    assert!(jumped, "buffered jump didn't happen after landing");
+}
+
+#[test]
+fn ground_jumps_cant_be_repeated_while_rising() {
+    let (mut harness, character) = landed_character_with(&grounded_jumps_only());
+
+    harness.press(character, PlayerAction::Jump);
+    harness.step(1);
+    let jump_velocity = harness.velocity(character).y;
+
+    // Tap jump again on the next tick, while the floor is still within reach
+    // of the ground caster
+    harness.release(character, PlayerAction::Jump);
+    harness.press(character, PlayerAction::Jump);
+    harness.step(1);
+
+    let velocity = harness.velocity(character);
+    assert!(
+        velocity.y < jump_velocity,
+        "jumped twice, velocity is {velocity}"
+    );
+}
@@ -0,0 +1,86 @@
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+use bevy_xpbd_3d::math::*;
+use holder::{
+    harness::SimulationHarness,
+    navigation::{NavMesh, NavMeshGeometry, NavMeshSettings},
+};
+
+/// Bakes a nav mesh from the static geometry spawned in the harness.
+fn bake(harness: &mut SimulationHarness) -> NavMesh {
+    // Let the colliders be added to the spatial query pipeline
+    harness.step(1);
+
+    harness
+        .app_mut()
+        .world
+        .run_system_once(|geometry: NavMeshGeometry| {
+            NavMesh::bake(&geometry, &NavMeshSettings::default())
+        })
+}
+
+/// The horizontal length of a path.
+fn length(path: &[Vector]) -> Scalar {
+    path.windows(2)
+        .map(|segment| (segment[1] - segment[0]) * Vector::new(1.0, 0.0, 1.0))
+        .map(|step| step.length())
+        .sum()
+}
+
+#[test]
+fn straight_paths_have_no_detours() {
+    let mut harness = SimulationHarness::new();
+    harness.spawn_box(Vector::NEG_Y * 0.5, Vector::new(10.0, 1.0, 10.0));
+    let nav_mesh = bake(&mut harness);
+
+    let start = Vector::new(-3.0, 0.0, 0.0);
+    let goal = Vector::new(3.0, 0.0, 0.0);
+    let path = nav_mesh
+        .find_path(start, goal)
+        .expect("goal should be reachable");
+
+    // Waypoints along the straight line are removed
+    assert_eq!(path.len(), 2, "path isn't straight: {path:?}");
+    assert!(path[0].distance(start) < 0.25 && path[1].distance(goal) < 0.25);
+    assert!(path.iter().all(|waypoint| waypoint.y.abs() < 0.01));
+}
+
+#[test]
+fn paths_go_around_walls() {
+    let mut harness = SimulationHarness::new();
+    harness.spawn_box(Vector::NEG_Y * 0.5, Vector::new(10.0, 1.0, 10.0));
+    // A wall between the start and the goal, with room to pass at either end
+    harness.spawn_box(Vector::Y, Vector::new(0.5, 2.0, 6.0));
+    let nav_mesh = bake(&mut harness);
+
+    let path = nav_mesh
+        .find_path(Vector::new(-3.0, 0.0, 0.0), Vector::new(3.0, 0.0, 0.0))
+        .expect("goal should be reachable around the wall");
+
+    assert!(path.len() > 2, "path doesn't turn: {path:?}");
+    assert!(
+        length(&path) > 6.0 + 2.0,
+        "path is too short to go around the wall: {path:?}"
+    );
+    for waypoint in &path {
+        let inside_wall = waypoint.x.abs() < 0.25 && waypoint.z.abs() < 3.0;
+        assert!(!inside_wall, "path goes through the wall at {waypoint}");
+    }
+}
+
+#[test]
+fn unreachable_goals_have_no_path() {
+    let mut harness = SimulationHarness::new();
+    // Two platforms separated by a gap
+    harness.spawn_box(Vector::new(-3.0, -0.5, 0.0), Vector::new(4.0, 1.0, 4.0));
+    harness.spawn_box(Vector::new(3.0, -0.5, 0.0), Vector::new(4.0, 1.0, 4.0));
+    let nav_mesh = bake(&mut harness);
+
+    assert!(nav_mesh.is_baked());
+    assert!(nav_mesh
+        .find_path(Vector::new(-3.0, 0.0, 0.0), Vector::new(-3.0, 0.0, 1.0))
+        .is_some());
+    assert_eq!(
+        nav_mesh.find_path(Vector::new(-3.0, 0.0, 0.0), Vector::new(3.0, 0.0, 0.0)),
+        None
+    );
+}
@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::math::*;
+use holder::{
+    character::{CharacterState, MovementMode, PlayerAction},
+    harness::SimulationHarness,
+    replay::{position_checksum, Replay, ReplayFrame, ReplayMode, ReplayPlugin, TickInput},
+};
+use leafwing_input_manager::prelude::*;
+
+#[test]
+fn replays_survive_a_round_trip() {
+    let replay = Replay {
+        start: vec![CharacterState {
+            position: Vector::new(0.0, 10.0, 0.0),
+            rotation: Quaternion::from_rotation_y(1.0),
+            linear_velocity: Vector::new(1.0, 2.0, 3.0),
+            mode: MovementMode::Crouch,
+            coyote_time: 0.05,
+            jump_buffer: 0.1,
+            air_jumps_remaining: 1,
+            grounded_on: Some(Entity::from_raw(7)),
+            jumping: true,
+        }],
+        frames: (0..3)
+            .map(|tick| ReplayFrame {
+                tick,
+                inputs: vec![TickInput {
+                    held: 0b101,
+                    jump_pressed: tick == 1,
+                    jump_released: tick == 2,
+                    direction: Vector::new(0.6, 0.0, -0.8),
+                }],
+                checksum: u64::from(tick) * 31,
+            })
+            .collect(),
+    };
+
+    let mut bytes = Vec::new();
+    replay.write_to(&mut bytes).unwrap();
+    let loaded = Replay::read_from(&mut bytes.as_slice()).unwrap();
+
+    assert_eq!(loaded.start, replay.start);
+    assert_eq!(loaded.frames.len(), replay.frames.len());
+    for (loaded, original) in loaded.frames.iter().zip(&replay.frames) {
+        assert_eq!(loaded.tick, original.tick);
+        assert_eq!(loaded.inputs, original.inputs);
+        assert_eq!(loaded.checksum, original.checksum);
+    }
+}
+
+/// Runs a scripted run-and-jump and returns the position checksum after every tick.
+fn simulate() -> Vec<u64> {
+    let mut harness = SimulationHarness::new();
+    harness.spawn_floor();
+    let character = harness.spawn_character(Vector::Y * 2.0);
+
+    let mut checksums = Vec::new();
+    for tick in 0..240 {
+        match tick {
+            60 => harness.set_movement(character, Vec2::new(0.5, 1.0)),
+            90 => harness.press(character, PlayerAction::Jump),
+            100 => harness.release(character, PlayerAction::Jump),
+            180 => harness.set_movement(character, Vec2::ZERO),
+            _ => {}
+        }
+        harness.step(1);
+        checksums.push(position_checksum([&harness.position(character)]));
+    }
+    checksums
+}
+
+#[test]
+fn simulations_are_deterministic() {
+    assert_eq!(simulate(), simulate());
+}
+
+/// A simulation with replays, whose mode is set directly instead of with F5 and F6.
+fn replay_harness() -> (SimulationHarness, Entity) {
+    let mut harness = SimulationHarness::new();
+    harness
+        .app_mut()
+        .init_resource::<Input<KeyCode>>()
+        .init_resource::<ToggleActions<PlayerAction>>()
+        .add_plugins(ReplayPlugin);
+    harness.spawn_floor();
+    let character = harness.spawn_character(Vector::Y * 2.0);
+    harness.step(60);
+    (harness, character)
+}
+
+fn replay_mode(harness: &mut SimulationHarness) -> Mut<ReplayMode> {
+    harness.app_mut().world.resource_mut::<ReplayMode>()
+}
+
+#[test]
+fn replays_reproduce_the_recording() {
+    let (mut harness, character) = replay_harness();
+
+    // Start recording crouched and in the middle of a jump,
+    // so more than the position has to be restored
+    harness.set_movement(character, Vec2::new(0.5, 1.0));
+    harness.press(character, PlayerAction::Crouch);
+    harness.step(30);
+    harness.press(character, PlayerAction::Jump);
+    harness.step(3);
+    *replay_mode(&mut harness) = ReplayMode::StartRecording;
+    harness.step(1);
+
+    for tick in 0..120 {
+        match tick {
+            5 => harness.release(character, PlayerAction::Jump),
+            20 => harness.release(character, PlayerAction::Crouch),
+            40 => harness.set_movement(character, Vec2::new(-1.0, 0.0)),
+            60 => harness.press(character, PlayerAction::Jump),
+            65 => harness.release(character, PlayerAction::Jump),
+            90 => harness.set_movement(character, Vec2::ZERO),
+            _ => {}
+        }
+        harness.step(1);
+    }
+    let recorded_end = harness.position(character);
+
+    let ReplayMode::Recording { replay, .. } = std::mem::take(&mut *replay_mode(&mut harness))
+    else {
+        panic!("the recording should still be running");
+    };
+    let frame_count = replay.frames.len();
+    assert_eq!(frame_count, 120);
+
+    // Wander off, so playback has to put the character back first
+    harness.set_movement(character, Vec2::new(1.0, -1.0));
+    harness.step(60);
+
+    // The starting state is restored on the first tick, and the recorded input
+    // is played back on the following ones
+    *replay_mode(&mut harness) = ReplayMode::StartPlaying { replay };
+    harness.step(frame_count);
+
+    let ReplayMode::Playing {
+        frame, diverged, ..
+    } = harness.app().world.resource::<ReplayMode>()
+    else {
+        panic!("the replay stopped early");
+    };
+    assert_eq!(*frame, frame_count - 1);
+    assert!(!diverged, "the replay diverged from the recording");
+
+    harness.step(1);
+    assert!(matches!(
+        harness.app().world.resource::<ReplayMode>(),
+        ReplayMode::Idle
+    ));
+    assert_eq!(harness.position(character), recorded_end);
+}